@@ -1,7 +1,285 @@
 extern crate knot;
-use knot::KnotHash;
+
+mod options;
+
+use knot::{KnotHash, KnotHashConfig, KnotHasher};
+use options::{unescape, Input, Mode, USAGE};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::exit;
 
 fn main() {
-    let hash = KnotHash::new("147,37,249,1,31,2,226,0,161,71,254,243,183,255,30,70");
-    println!("The hash of the puzzle input is {}.", hash);
+    match run() {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run() -> Result<bool, Box<dyn Error>> {
+    let options = options::parse_args(env::args().skip(1))?;
+
+    let success = match options.mode {
+        Mode::Help => {
+            println!("{}", USAGE);
+            true
+        }
+        Mode::Hash => print_hashes(&options.inputs),
+        Mode::PartOne => print_part_one_products(&options.inputs),
+        Mode::Check => check_all(&options.inputs),
+    };
+
+    Ok(success)
+}
+
+fn open(input: &Input) -> io::Result<Box<dyn Read>> {
+    let reader: Box<dyn Read> = match *input {
+        Input::Text(ref text) => Box::new(io::Cursor::new(text.clone().into_bytes())),
+        Input::File(ref path) => Box::new(BufReader::new(File::open(path)?)),
+        Input::Stdin => Box::new(io::stdin()),
+    };
+
+    Ok(reader)
+}
+
+fn hash_input(input: &Input) -> io::Result<KnotHash> {
+    let mut hasher = KnotHasher::new();
+    io::copy(&mut open(input)?, &mut hasher)?;
+
+    Ok(hasher.finalize())
+}
+
+fn print_hashes(inputs: &[Input]) -> bool {
+    let mut success = true;
+
+    for input in inputs {
+        match hash_input(input) {
+            Ok(hash) => println!("{}  {}", hash, input),
+            Err(e) => {
+                eprintln!("knot_hash: {}: {}", input, e);
+                success = false;
+            }
+        }
+    }
+
+    success
+}
+
+fn parse_lengths(text: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(',')
+        .map(|length| {
+            length
+                .trim()
+                .parse::<u8>()
+                .map_err(|_| From::from(format!("invalid length '{}'", length.trim())))
+        })
+        .collect()
+}
+
+fn part_one_product(ring_size: usize, lengths: &[u8]) -> u32 {
+    let hash = KnotHashConfig::new()
+        .ring_size(ring_size)
+        .rounds(1)
+        .suffix(&[])
+        .block_size(1)
+        .hash(lengths);
+    let numbers = hash.as_bytes();
+
+    u32::from(numbers[0]) * u32::from(numbers[1])
+}
+
+fn part_one(input: &Input) -> Result<u32, Box<dyn Error>> {
+    let mut text = String::new();
+    open(input)?.read_to_string(&mut text)?;
+    let lengths = parse_lengths(&text)?;
+
+    Ok(part_one_product(256, &lengths))
+}
+
+fn print_part_one_products(inputs: &[Input]) -> bool {
+    let mut success = true;
+
+    for input in inputs {
+        match part_one(input) {
+            Ok(product) => println!("{}  {}", product, input),
+            Err(e) => {
+                eprintln!("knot_hash: {}: {}", input, e);
+                success = false;
+            }
+        }
+    }
+
+    success
+}
+
+fn parse_check_line(line: &str) -> Option<(KnotHash, Input)> {
+    let mut parts = line.trim_end().splitn(2, char::is_whitespace);
+    let hash = parts.next()?.parse().ok()?;
+    let name = parts.next()?.trim_start();
+
+    let input = if name.is_empty() {
+        return None;
+    } else if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') {
+        Input::Text(unescape(&name[1..name.len() - 1])?)
+    } else if name == "-" {
+        Input::Stdin
+    } else {
+        Input::File(name.to_string())
+    };
+
+    Some((hash, input))
+}
+
+#[derive(Debug, Default)]
+struct CheckSummary {
+    malformed: usize,
+    unreadable: usize,
+    mismatched: usize,
+}
+
+fn check(list: &Input, summary: &mut CheckSummary) -> io::Result<()> {
+    let reader = BufReader::new(open(list)?);
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (expected, input) = match parse_check_line(&line) {
+            Some(entry) => entry,
+            None => {
+                summary.malformed += 1;
+                continue;
+            }
+        };
+
+        match hash_input(&input) {
+            Ok(ref hash) if *hash == expected => println!("{}: OK", input),
+            Ok(_) => {
+                println!("{}: FAILED", input);
+                summary.mismatched += 1;
+            }
+            Err(e) => {
+                eprintln!("knot_hash: {}: {}", input, e);
+                println!("{}: FAILED open or read", input);
+                summary.unreadable += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_all(lists: &[Input]) -> bool {
+    let mut summary = CheckSummary::default();
+    let mut success = true;
+
+    for list in lists {
+        if let Err(e) = check(list, &mut summary) {
+            eprintln!("knot_hash: {}: {}", list, e);
+            success = false;
+        }
+    }
+
+    if summary.malformed > 0 {
+        eprintln!(
+            "knot_hash: WARNING: {} line(s) are improperly formatted",
+            summary.malformed
+        );
+    }
+
+    if summary.unreadable > 0 {
+        eprintln!(
+            "knot_hash: WARNING: {} listed input(s) could not be read",
+            summary.unreadable
+        );
+    }
+
+    if summary.mismatched > 0 {
+        eprintln!(
+            "knot_hash: WARNING: {} computed hash(es) did NOT match",
+            summary.mismatched
+        );
+    }
+
+    success && summary.malformed == 0 && summary.unreadable == 0 && summary.mismatched == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_one_example() {
+        assert_eq!(part_one_product(5, &[3, 4, 1, 5]), 12);
+    }
+
+    #[test]
+    fn parse_puzzle_lengths() {
+        let lengths = parse_lengths("147,37, 249,1\n").unwrap();
+        assert_eq!(lengths, vec![147, 37, 249, 1]);
+    }
+
+    #[test]
+    fn parse_invalid_length() {
+        assert!(parse_lengths("1,256").is_err());
+        assert!(parse_lengths("1,,2").is_err());
+    }
+
+    #[test]
+    fn hash_text_input() {
+        let hash = hash_input(&Input::Text("AoC 2017".to_string())).unwrap();
+        assert_eq!(hash.to_string(), "33efeb34ea91902bb2f59c9920caa6cd");
+    }
+
+    #[test]
+    fn parse_check_line_for_file() {
+        let (hash, input) = parse_check_line("33efeb34ea91902bb2f59c9920caa6cd  input.txt").unwrap();
+        assert_eq!(hash, KnotHash::new("AoC 2017"));
+        assert_eq!(input, Input::File("input.txt".to_string()));
+    }
+
+    #[test]
+    fn parse_check_line_for_text() {
+        let (_, input) = parse_check_line("3efbe78a8d82f29979031a4aa0b16a9d  \"1,2,3\"").unwrap();
+        assert_eq!(input, Input::Text("1,2,3".to_string()));
+    }
+
+    #[test]
+    fn parse_malformed_check_lines() {
+        assert!(parse_check_line("33efeb34ea91902bb2f59c9920caa6cd").is_none());
+        assert!(parse_check_line("not-a-hash  input.txt").is_none());
+    }
+
+    #[test]
+    fn printed_hash_can_be_checked() {
+        let input = Input::Text("wenycdww".to_string());
+        let line = format!("{}  {}", hash_input(&input).unwrap(), input);
+        let (hash, parsed) = parse_check_line(&line).unwrap();
+
+        assert_eq!(parsed, input);
+        assert_eq!(hash, hash_input(&parsed).unwrap());
+    }
+
+    #[test]
+    fn printed_hash_of_quoted_text_can_be_checked() {
+        let input = Input::Text("\"1,2\" \\ 3\n".to_string());
+        let line = format!("{}  {}", hash_input(&input).unwrap(), input);
+        let (hash, parsed) = parse_check_line(&line).unwrap();
+
+        assert_eq!(parsed, input);
+        assert_eq!(hash, hash_input(&parsed).unwrap());
+        assert!(parse_check_line("3efbe78a8d82f29979031a4aa0b16a9d  \"1\"2\"").is_none());
+    }
 }
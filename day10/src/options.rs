@@ -0,0 +1,245 @@
+use std::error::Error;
+use std::fmt;
+
+pub const USAGE: &str = "Usage: knot_hash [OPTIONS] [FILE]...
+Print or check knot hashes.
+
+With no FILE, or when FILE is -, read standard input.
+
+  -s, --string TEXT  hash TEXT instead of a file; may be repeated
+  -c, --check        read knot hashes from the FILEs and check them
+  -1, --part1        treat each input as comma-separated lengths, tie a single
+                     round of knots and print the product of the first two
+                     numbers in the list
+  -h, --help         display this help and exit";
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Input {
+    Text(String),
+    File(String),
+    Stdin,
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Input::Text(ref text) => write!(f, "\"{}\"", escape(text)),
+            Input::File(ref path) => write!(f, "{}", path),
+            Input::Stdin => write!(f, "-"),
+        }
+    }
+}
+
+// Backslashes, quotes and newlines in text inputs are escaped when they are
+// displayed, so that check lines can quote any text on a single line.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// Reverses `escape`, or returns None if the text has a quote that is not
+// escaped or an escape sequence that `escape` never writes.
+pub fn unescape(escaped: &str) -> Option<String> {
+    let mut text = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => text.push('\\'),
+                '"' => text.push('"'),
+                'n' => text.push('\n'),
+                _ => return None,
+            },
+            '"' => return None,
+            _ => text.push(c),
+        }
+    }
+
+    Some(text)
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Mode {
+    Hash,
+    Check,
+    PartOne,
+    Help,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Options {
+    pub mode: Mode,
+    pub inputs: Vec<Input>,
+}
+
+#[derive(Debug)]
+pub struct UsageError {
+    message: String,
+}
+
+impl UsageError {
+    fn new(message: &str) -> UsageError {
+        UsageError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\nTry 'knot_hash --help' for more information.", self.message)
+    }
+}
+
+impl Error for UsageError {
+    fn description(&self) -> &str {
+        "Invalid command line"
+    }
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, UsageError> {
+    let mut check = false;
+    let mut part_one = false;
+    let mut inputs = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(Options {
+                    mode: Mode::Help,
+                    inputs: Vec::new(),
+                })
+            }
+            "-c" | "--check" => check = true,
+            "-1" | "--part1" => part_one = true,
+            "-s" | "--string" => match args.next() {
+                Some(text) => inputs.push(Input::Text(text)),
+                None => return Err(UsageError::new("option requires an argument -- 's'")),
+            },
+            "-" => inputs.push(Input::Stdin),
+            _ if arg.starts_with('-') => {
+                return Err(UsageError::new(&format!("unrecognized option '{}'", arg)))
+            }
+            _ => inputs.push(Input::File(arg)),
+        }
+    }
+
+    if check && part_one {
+        return Err(UsageError::new(
+            "the --check and --part1 options cannot be used together",
+        ));
+    }
+
+    if check && inputs.iter().any(|input| matches!(*input, Input::Text(_))) {
+        return Err(UsageError::new(
+            "the --string option cannot be used when checking hashes",
+        ));
+    }
+
+    if inputs.is_empty() {
+        inputs.push(Input::Stdin);
+    }
+
+    let mode = if check {
+        Mode::Check
+    } else if part_one {
+        Mode::PartOne
+    } else {
+        Mode::Hash
+    };
+
+    Ok(Options { mode, inputs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, UsageError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_reads_stdin() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.mode, Mode::Hash);
+        assert_eq!(options.inputs, vec![Input::Stdin]);
+    }
+
+    #[test]
+    fn files_and_strings_in_order() {
+        let options = parse(&["a.txt", "-s", "AoC 2017", "-", "--string", "1,2,3"]).unwrap();
+        assert_eq!(
+            options.inputs,
+            vec![
+                Input::File("a.txt".to_string()),
+                Input::Text("AoC 2017".to_string()),
+                Input::Stdin,
+                Input::Text("1,2,3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_mode() {
+        let options = parse(&["--check", "hashes.txt"]).unwrap();
+        assert_eq!(options.mode, Mode::Check);
+        assert_eq!(options.inputs, vec![Input::File("hashes.txt".to_string())]);
+    }
+
+    #[test]
+    fn part_one_mode() {
+        let options = parse(&["-1", "-s", "3,4,1,5"]).unwrap();
+        assert_eq!(options.mode, Mode::PartOne);
+    }
+
+    #[test]
+    fn help_stops_argument_parsing() {
+        let options = parse(&["-c", "--help", "--bogus"]).unwrap();
+        assert_eq!(options.mode, Mode::Help);
+    }
+
+    #[test]
+    fn unrecognized_option_is_an_error() {
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn string_without_text_is_an_error() {
+        assert!(parse(&["-s"]).is_err());
+    }
+
+    #[test]
+    fn check_and_part_one_conflict() {
+        assert!(parse(&["-c", "-1"]).is_err());
+    }
+
+    #[test]
+    fn input_display_names() {
+        assert_eq!(Input::Text("abc".to_string()).to_string(), "\"abc\"");
+        assert_eq!(Input::File("abc".to_string()).to_string(), "abc");
+        assert_eq!(Input::Stdin.to_string(), "-");
+    }
+
+    #[test]
+    fn text_inputs_are_escaped() {
+        let text = "say \"hi\"\\\nbye";
+        let displayed = Input::Text(text.to_string()).to_string();
+        assert_eq!(displayed, "\"say \\\"hi\\\"\\\\\\nbye\"");
+        assert_eq!(unescape(&displayed[1..displayed.len() - 1]), Some(text.to_string()));
+        assert_eq!(unescape("a\"b"), None);
+        assert_eq!(unescape("a\\"), None);
+        assert_eq!(unescape("a\\t"), None);
+    }
+}
@@ -1,19 +1,19 @@
+#[cfg(test)]
 #[macro_use]
 extern crate lazy_static;
 
 extern crate bit_tools;
 extern crate knot;
 
-use std::collections::HashSet;
 use knot::KnotHash;
-use bit_tools::{AsBits, Bit};
+use bit_tools::{AsBits, BitGrid, Connectivity};
 
 fn main() {
     let puzzle_input = "wenycdww";
 
-    let squares = construct_hashes(puzzle_input);
+    let squares = construct_grid(puzzle_input);
 
-    let squares_used = count_ones(&squares);
+    let squares_used = squares.count_ones();
 
     println!("{} squares are used.", squares_used);
 
@@ -22,84 +22,36 @@ fn main() {
     println!("There are {} regions.", regions);
 }
 
-fn construct_hashes(seed: &str) -> Vec<Vec<Bit>> {
-    let mut hashes: Vec<Vec<Bit>> = Vec::with_capacity(128);
-    for n in 0..128 {
-        let hash_input = format!("{}-{}", seed, n);
-        hashes.push(KnotHash::new(&hash_input).as_bytes().as_bits());
-    }
-
-    hashes
-}
-
-fn count_ones(squares: &[Vec<Bit>]) -> usize {
-    squares
-        .iter()
-        .map(|bits| bits.iter().filter(|&bit| *bit == Bit::One).count())
-        .sum()
-}
-
-fn count_regions(squares: &[Vec<Bit>]) -> usize {
-    let mut regions: usize = 0;
-    let mut already_counted: HashSet<(usize, usize)> = HashSet::new();
-
-    for (row_index, row) in squares.iter().enumerate() {
-        for (column_index, bit) in row.iter().enumerate() {
-            if *bit == Bit::Zero || already_counted.contains(&(row_index, column_index)) {
-                continue;
-            }
-
-            regions += 1;
-            mark_neighbours_counted(row_index, column_index, squares, &mut already_counted);
-        }
-    }
+fn construct_grid(seed: &str) -> BitGrid {
+    let hash_inputs: Vec<String> = (0..128).map(|n| format!("{}-{}", seed, n)).collect();
 
-    regions
+    BitGrid::from_rows(
+        KnotHash::par_hash_all(hash_inputs)
+            .iter()
+            .map(|hash| hash.as_bytes().as_bits()),
+    )
 }
 
-fn mark_neighbours_counted(
-    row: usize,
-    col: usize,
-    squares: &[Vec<Bit>],
-    already_counted: &mut HashSet<(usize, usize)>,
-) {
-    if squares[row][col] == Bit::Zero || already_counted.contains(&(row, col)) {
-        return;
-    }
-
-    already_counted.insert((row, col));
-    if row > 0 {
-        mark_neighbours_counted(row - 1, col, squares, already_counted);
-    }
-
-    if row < squares.len() - 1 {
-        mark_neighbours_counted(row + 1, col, squares, already_counted);
-    }
-
-    if col > 0 {
-        mark_neighbours_counted(row, col - 1, squares, already_counted);
-    }
-
-    if col < squares[row].len() - 1 {
-        mark_neighbours_counted(row, col + 1, squares, already_counted);
-    }
+fn count_regions(squares: &BitGrid) -> usize {
+    squares.label_components(Connectivity::Four).count()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bit_tools::{Bit, BitVec};
 
     lazy_static! {
-        static ref SQUARES: Vec<Vec<Bit>> = {
-            construct_hashes("flqrgnkx")
+        static ref SQUARES: BitGrid = {
+            construct_grid("flqrgnkx")
         };
     }
 
     #[test]
     fn example_correct_bit_sequence() {
         assert_eq!(
-            SQUARES[0][0..8],
-            [
+            SQUARES.row(0).slice(0..8),
+            vec![
                 Bit::One,
                 Bit::One,
                 Bit::Zero,
@@ -112,8 +64,8 @@ mod tests {
         );
 
         assert_eq!(
-            SQUARES[1][0..8],
-            [
+            SQUARES.row(1).slice(0..8),
+            vec![
                 Bit::Zero,
                 Bit::One,
                 Bit::Zero,
@@ -126,8 +78,8 @@ mod tests {
         );
 
         assert_eq!(
-            SQUARES[2][0..8],
-            [
+            SQUARES.row(2).slice(0..8),
+            vec![
                 Bit::Zero,
                 Bit::Zero,
                 Bit::Zero,
@@ -142,7 +94,7 @@ mod tests {
 
     #[test]
     fn part_one_example() {
-        assert_eq!(count_ones(&SQUARES), 8108);
+        assert_eq!(SQUARES.count_ones(), 8108);
     }
 
     #[test]
@@ -152,12 +104,15 @@ mod tests {
 
     #[test]
     fn count_two_regions() {
-        let squares = vec![
-            vec![Bit::One, Bit::Zero, Bit::Zero],
-            vec![Bit::Zero, Bit::Zero, Bit::Zero],
-            vec![Bit::Zero, Bit::One, Bit::One],
-            vec![Bit::Zero, Bit::One, Bit::One],
-        ];
+        let squares = BitGrid::from_rows(
+            vec![
+                vec![Bit::One, Bit::Zero, Bit::Zero],
+                vec![Bit::Zero, Bit::Zero, Bit::Zero],
+                vec![Bit::Zero, Bit::One, Bit::One],
+                vec![Bit::Zero, Bit::One, Bit::One],
+            ].into_iter()
+                .map(|row| row.into_iter().collect::<BitVec>()),
+        );
 
         assert_eq!(count_regions(&squares), 2);
     }
@@ -0,0 +1,46 @@
+use super::Instruction;
+use register_vm::bytecode::{compile_register, Slot, Source};
+
+// An instruction with its registers resolved to slots.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Op {
+    Add(Slot, Source),
+    Modulus(Slot, Source),
+    Multiply(Slot, Source),
+    Receive(Slot),
+    Set(Slot, Source),
+    Send(Source),
+    JumpIfGreaterThanZero(Source, Source),
+}
+
+pub fn compile(instruction: &Instruction) -> Op {
+    match *instruction {
+        Instruction::Add(r, ref v) => Op::Add(compile_register(r), Source::compile(v)),
+        Instruction::Modulus(r, ref v) => Op::Modulus(compile_register(r), Source::compile(v)),
+        Instruction::Multiply(r, ref v) => Op::Multiply(compile_register(r), Source::compile(v)),
+        Instruction::Receive(r) => Op::Receive(compile_register(r)),
+        Instruction::Set(r, ref v) => Op::Set(compile_register(r), Source::compile(v)),
+        Instruction::Send(ref v) => Op::Send(Source::compile(v)),
+        Instruction::JumpIfGreaterThanZero(ref x, ref y) => {
+            Op::JumpIfGreaterThanZero(Source::compile(x), Source::compile(y))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_registers() {
+        let compile_line = |line: &str| compile(&line.parse().unwrap());
+
+        assert_eq!(compile_line("add a 2"), Op::Add(0, Source::Literal(2)));
+        assert_eq!(compile_line("mod p b"), Op::Modulus(15, Source::Register(1)));
+        assert_eq!(compile_line("rcv z"), Op::Receive(25));
+        assert_eq!(
+            compile_line("jgz 1 c"),
+            Op::JumpIfGreaterThanZero(Source::Literal(1), Source::Register(2))
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use super::{ErrorKind, Instruction};
+use std::fmt;
+use std::iter;
+
+// A parse error located in the source text. Lines and columns count from 1,
+// and `width` is the number of characters to underline.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub kind: ErrorKind,
+    pub line: usize,
+    pub column: usize,
+    pub width: usize,
+    pub source: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        // Keep any tabs from the source line, so that the carets line up
+        // under it however wide the tabs are shown.
+        let indent: String = self
+            .source
+            .chars()
+            .chain(iter::repeat(' '))
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "error: {}", self.kind)?;
+        writeln!(f, "{}--> {}:{}", gutter, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_number, self.source)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            indent,
+            "^".repeat(self.width)
+        )
+    }
+}
+
+// Each whitespace-separated token on a line, with the column it starts at.
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (index, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                tokens.push((s, &line[s..index]));
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start {
+        tokens.push((s, &line[s..]));
+    }
+
+    tokens
+        .into_iter()
+        .map(|(offset, token)| (line[..offset].chars().count() + 1, token))
+        .collect()
+}
+
+fn locate(kind: ErrorKind, token: usize, line_number: usize, line: &str) -> Diagnostic {
+    let tokens = tokens(line);
+
+    let (column, width) = match tokens.get(token) {
+        Some(&(column, text)) if kind != ErrorKind::MissingArgument => {
+            (column, text.chars().count())
+        }
+        // The argument that should be there would come just after the last one.
+        _ => {
+            let &(column, text) = tokens.last().unwrap();
+            (column + text.chars().count() + 1, 1)
+        }
+    };
+
+    Diagnostic {
+        kind,
+        line: line_number,
+        column,
+        width,
+        source: line.to_string(),
+    }
+}
+
+// Parses a whole program, skipping blank lines, and reports every line that
+// could not be parsed rather than stopping at the first.
+pub fn parse_program(source: &str) -> Result<Vec<Instruction>, Vec<Diagnostic>> {
+    let mut instructions = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match line.parse::<Instruction>() {
+            Ok(instruction) => instructions.push(instruction),
+            Err(e) => diagnostics.push(locate(e.kind, e.token, index + 1, line)),
+        }
+    }
+
+    if diagnostics.is_empty() {
+        Ok(instructions)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Value;
+
+    #[test]
+    fn valid_program() {
+        let program = parse_program("set a 1\n\nsnd a\n").unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::Set('a', Value::Literal(1)),
+                Instruction::Send(Value::FromRegister('a')),
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_every_error() {
+        let source = "set a 1\nfoo a 2\nadd a\nrcv 5\nsnd a";
+        let diagnostics = parse_program(source).unwrap_err();
+
+        let found: Vec<(ErrorKind, usize, usize, usize)> = diagnostics
+            .iter()
+            .map(|d| (d.kind, d.line, d.column, d.width))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (ErrorKind::UnrecognizedInstruction, 2, 1, 3),
+                (ErrorKind::MissingArgument, 3, 7, 1),
+                (ErrorKind::WrongArgumentType, 4, 5, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_allow_for_extra_whitespace() {
+        let diagnostics = parse_program("  mul\t 12 3").unwrap_err();
+        assert_eq!(diagnostics[0].column, 8);
+        assert_eq!(diagnostics[0].width, 2);
+    }
+
+    #[test]
+    fn render_with_caret() {
+        let diagnostics = parse_program("snd a\nmod 7 b").unwrap_err();
+        assert_eq!(
+            diagnostics[0].to_string(),
+            [
+                "error: Literal value supplied where register required",
+                " --> 2:5",
+                "  |",
+                "2 | mod 7 b",
+                "  |     ^",
+            ].join("\n")
+        );
+    }
+
+    #[test]
+    fn render_carets_under_tabs() {
+        let diagnostics = parse_program("\tmul\t12 3\nadd a").unwrap_err();
+        assert_eq!(
+            diagnostics[0].to_string(),
+            [
+                "error: Literal value supplied where register required",
+                " --> 1:6",
+                "  |",
+                "1 | \tmul\t12 3",
+                "  | \t   \t^^",
+            ].join("\n")
+        );
+        assert!(diagnostics[1].to_string().ends_with("\n  |       ^"));
+    }
+}
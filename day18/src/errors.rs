@@ -0,0 +1,87 @@
+use register_vm::{Halted, Register};
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum RuntimeErrorKind {
+    Overflow,
+    DivisionByZero,
+    JumpOutOfRange,
+    // Stopped for exceeding its execution budget.
+    Halted(Halted),
+    // A network's topology sends messages to the program at this position,
+    // but the network has no program there.
+    NoSuchDestination(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    // The ID of the program that failed, when it was one of several in a
+    // network.
+    pub program: Option<i64>,
+    pub index: usize,
+    pub registers: HashMap<Register, i64>,
+}
+
+impl RuntimeError {
+    pub fn new(
+        kind: RuntimeErrorKind,
+        index: usize,
+        registers: &HashMap<Register, i64>,
+    ) -> RuntimeError {
+        RuntimeError {
+            kind,
+            program: None,
+            index,
+            registers: registers.clone(),
+        }
+    }
+
+    pub fn in_program(self, id: i64) -> RuntimeError {
+        RuntimeError {
+            program: Some(id),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(id) = self.program {
+            write!(f, "Program {}: ", id)?;
+        }
+
+        match self.kind {
+            RuntimeErrorKind::Overflow => write!(f, "Arithmetic overflow")?,
+            RuntimeErrorKind::DivisionByZero => write!(f, "Modulus by zero")?,
+            RuntimeErrorKind::JumpOutOfRange => write!(f, "Jump out of range")?,
+            RuntimeErrorKind::Halted(halted) => write!(f, "{}", halted)?,
+            RuntimeErrorKind::NoSuchDestination(destination) => {
+                return write!(f, "Sends to program {}, which is not in the network", destination)
+            }
+        }
+
+        write!(f, " at instruction {}", self.index)?;
+
+        let mut registers: Vec<(&Register, &i64)> = self.registers.iter().collect();
+        registers.sort();
+        for (n, &(register, value)) in registers.iter().enumerate() {
+            let separator = if n == 0 { " (" } else { ", " };
+            write!(f, "{}{} = {}", separator, register, value)?;
+        }
+
+        if !registers.is_empty() {
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for RuntimeError {
+    fn description(&self) -> &str {
+        "Runtime error"
+    }
+}
@@ -1,82 +1,23 @@
+#[cfg(test)]
+extern crate proptest;
+extern crate register_vm;
+
+mod bytecode;
+mod diagnostics;
+mod errors;
+mod machine;
+mod network;
+
+pub use bytecode::Op;
+pub use diagnostics::{parse_program, Diagnostic};
+pub use errors::{RuntimeError, RuntimeErrorKind};
+pub use machine::{ArithmeticMode, Machine, Status};
+pub use network::{Network, Topology};
+pub use register_vm::{Budget, Error, ErrorKind, Halted, Register, Trace, Value};
+use register_vm::{InstructionSet, Opcode, Operand, Watchdog};
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::fmt;
-use std::sync::mpsc;
-use std::sync::mpsc::{Sender, Receiver};
-use std::time::Duration;
-use std::thread;
-
-pub type Register = char;
-
-#[derive(Debug, Eq, PartialEq)]
-pub enum ErrorKind {
-    UnrecognizedInstruction,
-    MissingArgument,
-    WrongArgumentType,
-}
-
-#[derive(Debug)]
-pub struct Error {
-    pub kind: ErrorKind,
-}
-
-impl Error {
-    fn missing_argument() -> Error {
-        Error {
-            kind: ErrorKind::MissingArgument,
-        }
-    }
-
-    fn unrecognized_instruction() -> Error {
-        Error {
-            kind: ErrorKind::UnrecognizedInstruction,
-        }
-    }
-
-    fn wrong_type() -> Error {
-        Error {
-            kind: ErrorKind::WrongArgumentType,
-        }
-    }
-}
-
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.kind {
-            ErrorKind::UnrecognizedInstruction => write!(f, "Unrecognized instruction"),
-            ErrorKind::MissingArgument => write!(f, "Missing argument"),
-            ErrorKind::WrongArgumentType => {
-                write!(f, "Literal value supplied where register required")
-            }
-        }
-    }
-}
-
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        "Invalid instruction"
-    }
-}
-
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub enum Value {
-    FromRegister(Register),
-    Literal(i64),
-}
-
-impl FromStr for Value {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(n) = s.parse() {
-            Ok(Value::Literal(n))
-        } else if let Some(c) = s.chars().nth(0) {
-            Ok(Value::FromRegister(c))
-        } else {
-            Err(Error::missing_argument())
-        }
-    }
-}
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum Instruction {
@@ -89,237 +30,199 @@ pub enum Instruction {
     JumpIfGreaterThanZero(Value, Value),
 }
 
-impl FromStr for Instruction {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split_whitespace().collect();
-        let length = parts.len();
-        if length < 2 {
-            return Err(Error::missing_argument());
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Add(r, ref v) => write!(f, "add {} {}", r, v),
+            Instruction::Modulus(r, ref v) => write!(f, "mod {} {}", r, v),
+            Instruction::Multiply(r, ref v) => write!(f, "mul {} {}", r, v),
+            Instruction::Receive(r) => write!(f, "rcv {}", r),
+            Instruction::Set(r, ref v) => write!(f, "set {} {}", r, v),
+            Instruction::Send(ref v) => write!(f, "snd {}", v),
+            Instruction::JumpIfGreaterThanZero(ref x, ref y) => write!(f, "jgz {} {}", x, y),
         }
+    }
+}
 
-        let instruction = parts[0];
-        let arg1: Value = parts[1].parse()?;
+static OPCODES: &[Opcode<Instruction>] = &[
+    Opcode {
+        mnemonic: "add",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Add(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "mod",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Modulus(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "mul",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Multiply(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "rcv",
+        operands: &[Operand::Register],
+        build: |a| Instruction::Receive(a.register(0)),
+    },
+    Opcode {
+        mnemonic: "set",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Set(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "snd",
+        operands: &[Operand::Value],
+        build: |a| Instruction::Send(a.value(0)),
+    },
+    Opcode {
+        mnemonic: "jgz",
+        operands: &[Operand::Value, Operand::Value],
+        build: |a| Instruction::JumpIfGreaterThanZero(a.value(0), a.value(1)),
+    },
+];
+
+impl InstructionSet for Instruction {
+    fn opcodes() -> &'static [Opcode<Instruction>] {
+        OPCODES
+    }
+}
 
-        let arg2: Option<Value> = if length > 2 {
-            Some(parts[2].parse()?)
-        } else {
-            None
-        };
+impl FromStr for Instruction {
+    type Err = Error;
 
-        match instruction {
-            "add" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Add(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "mod" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Modulus(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "mul" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Multiply(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "rcv" => match arg1 {
-                Value::FromRegister(r) => Ok(Instruction::Receive(r)),
-                Value::Literal(_) => Err(Error::wrong_type())
-            },
-            "set" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Set(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "snd" => Ok(Instruction::Send(arg1)),
-            "jgz" => match arg2 {
-                Some(v) => Ok(Instruction::JumpIfGreaterThanZero(arg1, v)),
-                None => Err(Error::missing_argument()),
-            },
-            _ => Err(Error::unrecognized_instruction()),
-        }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        register_vm::parse(s)
     }
 }
 
 
-struct Registers {
-    registers: HashMap<Register, i64>,
+pub fn play(instructions: &[Instruction]) -> Result<Option<i64>, RuntimeError> {
+    play_with_budget(instructions, &Budget::default())
 }
 
-impl Registers {
-    fn new() -> Registers {
-        Registers {
-            registers: HashMap::new(),
+// Like `play`, but gives up with a `RuntimeErrorKind::Halted` error if the
+// program exceeds the budget.
+pub fn play_with_budget(
+    instructions: &[Instruction],
+    budget: &Budget,
+) -> Result<Option<i64>, RuntimeError> {
+    let mut machine = Machine::new(instructions);
+    let mut last_sound: Option<i64> = None;
+    let mut watchdog = Watchdog::new(budget);
+
+    loop {
+        if let Err(halted) = watchdog.tick(&(&machine, last_sound)) {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::Halted(halted),
+                machine.program_counter(),
+                &machine.registers(),
+            ));
         }
-    }
 
-    fn with_id(id: i64) -> Registers {
-        let mut map = HashMap::new();
-        map.insert('p', id);
+        // In part 1, rcv recovers the last sound played rather than waiting
+        // for a message, and does nothing if its register is not positive.
+        if let Some(&Instruction::Receive(register)) = machine.current_instruction() {
+            if machine.register(register) > 0 {
+                return Ok(last_sound);
+            }
 
-        Registers {
-            registers: map
+            machine.skip();
+            continue;
         }
-    }
 
-    fn get_value(&self, value: &Value) -> i64 {
-        match *value {
-            Value::FromRegister(r) => *self.registers.get(&r).unwrap_or(&0),
-            Value::Literal(v) => v,
+        match machine.step()? {
+            Status::Sent(sound) => {
+                machine.take_output();
+                last_sound = Some(sound);
+            }
+            Status::Halted => return Ok(None),
+            _ => {}
         }
     }
-
-    fn get_mut(&mut self, register: &Register) -> &mut i64 {
-        self.registers.entry(register.clone()).or_insert(0)
-    }
 }
 
-pub fn play(instructions: &[Instruction]) -> Option<i64> {
-    let mut registers = Registers::new();
-    let mut last_sound: Option<i64> = None;
-    let mut index = 0;
-
-    while index < instructions.len() {
-        match instructions[index] {
-            Instruction::Add(ref register, ref value) => {
-                *registers.get_mut(register) += registers.get_value(value);
-            }
-            Instruction::Modulus(ref register, ref value) => {
-                let modulo = registers.get_value(value);
-                *registers.get_mut(register) %= modulo;
-            }
-            Instruction::Multiply(ref register, ref value) => {
-                let factor = registers.get_value(value);
-                *registers.get_mut(register) *= factor;
-            }
-            Instruction::Receive(ref register) => {
-                if *registers.get_mut(register) > 0 {
-                    return last_sound;
-                }
-            }
-            Instruction::Set(ref register, ref value) => {
-                *registers.get_mut(register) = registers.get_value(value);
-            }
-            Instruction::Send(ref value) => {
-                last_sound = Some(registers.get_value(value));
-            }
-            Instruction::JumpIfGreaterThanZero(_, _) => {}
-        }
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ProgramReport {
+    pub id: i64,
+    pub messages_sent: usize,
+    pub registers: HashMap<Register, i64>,
+    // Only recorded if the network was asked to trace its programs.
+    pub trace: Option<Trace>,
+}
 
-        let increment = match instructions[index] {
-            Instruction::JumpIfGreaterThanZero(ref condition, ref jump) => {
-                if registers.get_value(condition) > 0 {
-                    registers.get_value(jump)
-                } else {
-                    1
-                }
-            }
-            _ => 1,
-        };
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DuetReport {
+    pub programs: Vec<ProgramReport>,
+}
 
-        index = (index as i64 + increment) as usize;
+impl DuetReport {
+    pub fn messages_sent_by(&self, id: i64) -> Option<usize> {
+        self.programs
+            .iter()
+            .find(|program| program.id == id)
+            .map(|program| program.messages_sent)
     }
-
-    None
 }
 
-fn count_messages(instructions: &[Instruction], id: i64, tx: Sender<i64>, rx: Receiver<i64>) {
-    let mut messages_sent: usize = 0;
-
-    let mut registers = Registers::with_id(id);
-    let mut index = 0;
+// Two copies of the program, with ids 0 and 1, that talk to each other.
+pub fn duet_network(instructions: &[Instruction]) -> Network<'_> {
+    let mut network = Network::new(Topology::Pairs);
+    network.add_program(instructions, 0);
+    network.add_program(instructions, 1);
+    network
+}
 
-    while index < instructions.len() {
-        match instructions[index] {
-            Instruction::Add(ref register, ref value) => {
-                *registers.get_mut(register) += registers.get_value(value);
-            }
-            Instruction::Modulus(ref register, ref value) => {
-                let modulo = registers.get_value(value);
-                *registers.get_mut(register) %= modulo;
-            }
-            Instruction::Multiply(ref register, ref value) => {
-                let factor = registers.get_value(value);
-                *registers.get_mut(register) *= factor;
-            }
-            Instruction::Receive(ref register) => {
-               match rx.recv_timeout(Duration::from_secs(1)) {
-                   Ok(value) => *registers.get_mut(register) = value,
-                   Err(_) => {
-                       println!("Receive timed out for program {}; terminating.", id);
-                       break;
-                   }
-               }
-            }
-            Instruction::Set(ref register, ref value) => {
-                *registers.get_mut(register) = registers.get_value(value);
-            }
-            Instruction::Send(ref value) => {
-                let to_send = registers.get_value(value);
-                match tx.send(to_send) {
-                    Ok(_) => messages_sent += 1,
-                    Err(_) => {
-                        println!("Program {} cannot send; terminating.", id);
-                        break; // Assume the other thread has hung up
-                    }
-                }
-            }
-            Instruction::JumpIfGreaterThanZero(_, _) => {}
-        }
+pub fn perform_duet(instructions: &[Instruction]) -> Result<DuetReport, RuntimeError> {
+    duet_network(instructions).run()
+}
 
-        let increment = match instructions[index] {
-            Instruction::JumpIfGreaterThanZero(ref condition, ref jump) => {
-                if registers.get_value(condition) > 0 {
-                    registers.get_value(jump)
-                } else {
-                    1
-                }
-            }
-            _ => 1,
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
 
-        index = (index as i64 + increment) as usize;
+    fn register() -> impl Strategy<Value = Register> {
+        (b'a'..=b'z').prop_map(char::from)
     }
 
-    println!("Program {} has sent {} messages.", id, messages_sent);
-}
-
-pub fn perform_duet(instructions: &[Instruction]) {
-    let mut instructions_0: Vec<Instruction> = Vec::with_capacity(instructions.len());
-    let mut instructions_1: Vec<Instruction> = Vec::with_capacity(instructions.len());
-    for instruction in instructions.iter() {
-        instructions_0.push(instruction.clone());
-        instructions_1.push(instruction.clone());
+    fn value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            register().prop_map(Value::FromRegister),
+            any::<i64>().prop_map(Value::Literal),
+        ]
     }
 
-    let (tx0, rx1): (Sender<i64>, Receiver<i64>) = mpsc::channel();
-    let (tx1, rx0): (Sender<i64>, Receiver<i64>) = mpsc::channel();
-
-    let thread0 = thread::spawn(move || {
-        count_messages(&instructions_0, 0, tx0, rx0);
-    });
-
-    let thread1 = thread::spawn(move || {
-        count_messages(&instructions_1, 1, tx1, rx1);
-    });
+    fn instruction() -> impl Strategy<Value = Instruction> {
+        prop_oneof![
+            (register(), value()).prop_map(|(r, v)| Instruction::Add(r, v)),
+            (register(), value()).prop_map(|(r, v)| Instruction::Modulus(r, v)),
+            (register(), value()).prop_map(|(r, v)| Instruction::Multiply(r, v)),
+            register().prop_map(Instruction::Receive),
+            (register(), value()).prop_map(|(r, v)| Instruction::Set(r, v)),
+            value().prop_map(Instruction::Send),
+            (value(), value()).prop_map(|(x, y)| Instruction::JumpIfGreaterThanZero(x, y)),
+        ]
+    }
 
-    thread0.join().unwrap();
-    thread1.join().unwrap();
-}
+    proptest! {
+        #[test]
+        fn print_then_parse(instruction in instruction()) {
+            let printed = instruction.to_string();
+            prop_assert_eq!(printed.parse::<Instruction>().unwrap(), instruction);
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn display_instructions() {
+        assert_eq!(Instruction::Add('a', Value::Literal(-2)).to_string(), "add a -2");
+        assert_eq!(Instruction::Receive('b').to_string(), "rcv b");
+        assert_eq!(
+            Instruction::JumpIfGreaterThanZero(Value::Literal(1), Value::FromRegister('c'))
+                .to_string(),
+            "jgz 1 c"
+        );
+    }
 
     #[test]
     fn play_example() {
@@ -336,7 +239,40 @@ mod tests {
             Instruction::JumpIfGreaterThanZero(Value::FromRegister('a'), Value::Literal(-2)),
         ];
 
-        assert_eq!(play(&instructions).unwrap(), 4);
+        assert_eq!(play(&instructions).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn play_with_budget_stops_endless_programs() {
+        // Plays a sound forever without ever recovering it.
+        let instructions: Vec<Instruction> = ["set a 1", "snd a", "jgz a -1"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect();
+
+        let budget = Budget {
+            max_steps: Some(100),
+            ..Budget::default()
+        };
+        let error = play_with_budget(&instructions, &budget).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::Halted(Halted::StepLimit));
+
+        let budget = Budget {
+            detect_loops: true,
+            ..Budget::default()
+        };
+        let error = play_with_budget(&instructions, &budget).unwrap_err();
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::Halted(Halted::LoopDetected {
+                first_seen: 2,
+                period: 2,
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Infinite loop: the state after step 2 recurs every 2 steps at instruction 2 (a = 1)"
+        );
     }
 
     #[test]
@@ -357,6 +293,52 @@ mod tests {
             Instruction::Receive('d')
         ];
 
-        perform_duet(&program);
+        let report = perform_duet(&program).unwrap();
+        assert_eq!(report.messages_sent_by(0), Some(3));
+        assert_eq!(report.messages_sent_by(1), Some(3));
+        assert_eq!(report.messages_sent_by(2), None);
+
+        let program_0 = &report.programs[0];
+        assert_eq!(program_0.registers.get(&'a'), Some(&1));
+        assert_eq!(program_0.registers.get(&'b'), Some(&2));
+        assert_eq!(program_0.registers.get(&'c'), Some(&1));
+        assert_eq!(program_0.registers.get(&'d'), None);
+
+        let program_1 = &report.programs[1];
+        assert_eq!(program_1.registers.get(&'p'), Some(&1));
+        assert_eq!(program_1.registers.get(&'c'), Some(&0));
+    }
+
+    #[test]
+    fn duet_terminates_when_both_programs_finish() {
+        let program = vec![
+            Instruction::Send(Value::FromRegister('p')),
+            Instruction::Receive('a'),
+            Instruction::Add('a', Value::Literal(10)),
+        ];
+
+        let report = perform_duet(&program).unwrap();
+        assert_eq!(report.programs[0].registers.get(&'a'), Some(&11));
+        assert_eq!(report.programs[1].registers.get(&'a'), Some(&10));
+    }
+
+    #[test]
+    fn duet_continues_after_one_program_terminates() {
+        let program = vec![
+            Instruction::JumpIfGreaterThanZero(Value::FromRegister('p'), Value::Literal(4)),
+            Instruction::Send(Value::Literal(5)),
+            Instruction::Send(Value::Literal(6)),
+            Instruction::JumpIfGreaterThanZero(Value::Literal(1), Value::Literal(10)),
+            Instruction::Receive('a'),
+            Instruction::Receive('b'),
+            Instruction::Receive('c'),
+        ];
+
+        let report = perform_duet(&program).unwrap();
+        assert_eq!(report.messages_sent_by(0), Some(2));
+        assert_eq!(report.messages_sent_by(1), Some(0));
+        assert_eq!(report.programs[1].registers.get(&'a'), Some(&5));
+        assert_eq!(report.programs[1].registers.get(&'b'), Some(&6));
+        assert_eq!(report.programs[1].registers.get(&'c'), None);
     }
 }
@@ -0,0 +1,455 @@
+use super::Instruction;
+use bytecode::{self, Op};
+use errors::{RuntimeError, RuntimeErrorKind};
+use register_vm::{Control, Execute, Interpreter, Outcome, Register, Registers, Trace};
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Status {
+    Running,
+    Blocked,
+    Halted,
+    Sent(i64),
+}
+
+// How add and mul behave when the result does not fit in an i64.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum ArithmeticMode {
+    Wrapping,
+    Saturating,
+    #[default]
+    Checked,
+}
+
+impl ArithmeticMode {
+    fn add(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            ArithmeticMode::Wrapping => Some(a.wrapping_add(b)),
+            ArithmeticMode::Saturating => Some(a.saturating_add(b)),
+            ArithmeticMode::Checked => a.checked_add(b),
+        }
+    }
+
+    fn multiply(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            ArithmeticMode::Wrapping => Some(a.wrapping_mul(b)),
+            ArithmeticMode::Saturating => Some(a.saturating_mul(b)),
+            ArithmeticMode::Checked => a.checked_mul(b),
+        }
+    }
+}
+
+// Everything a duet program has besides its registers.
+#[derive(Debug, Clone, Default)]
+pub struct Channels {
+    inbox: VecDeque<i64>,
+    outbox: VecDeque<i64>,
+    messages_sent: usize,
+    arithmetic_mode: ArithmeticMode,
+}
+
+impl Execute for Instruction {
+    type State = Channels;
+    type Error = RuntimeErrorKind;
+    type Op = Op;
+
+    fn compile(&self) -> Op {
+        bytecode::compile(self)
+    }
+
+    fn execute(
+        op: &Op,
+        registers: &mut Registers,
+        channels: &mut Channels,
+    ) -> Result<Control, RuntimeErrorKind> {
+        let mode = channels.arithmetic_mode;
+
+        match *op {
+            Op::Add(x, value) => {
+                let sum = mode.add(registers.load(x), registers.source(value))
+                    .ok_or(RuntimeErrorKind::Overflow)?;
+                registers.store(x, sum);
+            }
+            Op::Modulus(x, value) => {
+                let modulo = registers.source(value);
+                if modulo == 0 {
+                    return Err(RuntimeErrorKind::DivisionByZero);
+                }
+
+                // The only case that overflows, i64::MIN % -1, is zero anyway.
+                let remainder = registers.load(x).wrapping_rem(modulo);
+                registers.store(x, remainder);
+            }
+            Op::Multiply(x, value) => {
+                let product = mode.multiply(registers.load(x), registers.source(value))
+                    .ok_or(RuntimeErrorKind::Overflow)?;
+                registers.store(x, product);
+            }
+            Op::Receive(x) => match channels.inbox.pop_front() {
+                Some(value) => registers.store(x, value),
+                None => return Ok(Control::Wait),
+            },
+            Op::Set(x, value) => {
+                let value = registers.source(value);
+                registers.store(x, value);
+            }
+            Op::Send(value) => {
+                channels.outbox.push_back(registers.source(value));
+                channels.messages_sent += 1;
+            }
+            Op::JumpIfGreaterThanZero(condition, offset) => {
+                if registers.source(condition) > 0 {
+                    return Ok(Control::Jump(registers.source(offset)));
+                }
+            }
+        }
+
+        Ok(Control::Next)
+    }
+
+    fn jump_before_start() -> Result<(), RuntimeErrorKind> {
+        Err(RuntimeErrorKind::JumpOutOfRange)
+    }
+}
+
+pub struct Machine<'a> {
+    interpreter: Interpreter<'a, Instruction>,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(instructions: &'a [Instruction]) -> Machine<'a> {
+        Machine {
+            interpreter: Interpreter::new(instructions, Channels::default()),
+        }
+    }
+
+    // A machine for part 2, whose register p holds its program ID.
+    pub fn with_id(instructions: &'a [Instruction], id: i64) -> Machine<'a> {
+        let mut machine = Machine::new(instructions);
+        machine.set_register('p', id);
+        machine
+    }
+
+    pub fn arithmetic_mode(&self) -> ArithmeticMode {
+        self.interpreter.state().arithmetic_mode
+    }
+
+    pub fn set_arithmetic_mode(&mut self, mode: ArithmeticMode) {
+        self.interpreter.state_mut().arithmetic_mode = mode;
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.interpreter.program_counter()
+    }
+
+    pub fn current_instruction(&self) -> Option<&'a Instruction> {
+        self.interpreter.current_instruction()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.interpreter.is_halted()
+    }
+
+    pub fn register(&self, register: Register) -> i64 {
+        self.interpreter.registers().get(register)
+    }
+
+    pub fn set_register(&mut self, register: Register, value: i64) {
+        self.interpreter.registers_mut().set(register, value);
+    }
+
+    pub fn registers(&self) -> HashMap<Register, i64> {
+        self.interpreter.registers().to_map()
+    }
+
+    // The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.interpreter.steps()
+    }
+
+    pub fn messages_sent(&self) -> usize {
+        self.interpreter.state().messages_sent
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.interpreter.state_mut().inbox.push_back(value);
+    }
+
+    pub fn inbox(&self) -> &VecDeque<i64> {
+        &self.interpreter.state().inbox
+    }
+
+    pub fn take_output(&mut self) -> Option<i64> {
+        self.interpreter.state_mut().outbox.pop_front()
+    }
+
+    pub fn outbox(&self) -> &VecDeque<i64> {
+        &self.interpreter.state().outbox
+    }
+
+    // Records every instruction executed from now on.
+    pub fn start_trace(&mut self) {
+        self.interpreter.start_trace();
+    }
+
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.interpreter.take_trace()
+    }
+
+    // Moves on to the next instruction without executing the current one.
+    pub fn skip(&mut self) {
+        self.interpreter.skip();
+    }
+
+    // Executes a single instruction. A `rcv` with nothing in the inbox leaves
+    // the machine where it is and reports that it is blocked; values sent are
+    // both returned and added to the outbox. Jumping past the end of the
+    // program halts the machine, but jumping before the start is an error.
+    pub fn step(&mut self) -> Result<Status, RuntimeError> {
+        if self.is_halted() {
+            return Ok(Status::Halted);
+        }
+
+        let index = self.program_counter();
+        let sent_before = self.messages_sent();
+
+        match self.interpreter.step() {
+            Ok(Outcome::Waiting) => Ok(Status::Blocked),
+            Ok(_) if self.messages_sent() > sent_before => {
+                Ok(Status::Sent(*self.outbox().back().unwrap()))
+            }
+            Ok(_) => Ok(Status::Running),
+            Err(kind) => Err(RuntimeError::new(kind, index, &self.registers())),
+        }
+    }
+
+    // Runs until the machine halts or has to wait for input, returning either
+    // `Status::Halted` or `Status::Blocked`.
+    pub fn run_until_blocked(&mut self) -> Result<Status, RuntimeError> {
+        loop {
+            match self.step()? {
+                Status::Running | Status::Sent(_) => {}
+                status => return Ok(status),
+            }
+        }
+    }
+}
+
+// Hashes everything that affects what the machine does next, for detecting
+// loops. The step and message counts are left out, as they never repeat.
+impl<'a> Hash for Machine<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.program_counter().hash(state);
+        self.interpreter.registers().hash(state);
+        self.inbox().hash(state);
+        self.outbox().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use register_vm::Value;
+
+    #[test]
+    fn step_reports_each_instruction() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(3)),
+            Instruction::Send(Value::FromRegister('a')),
+            Instruction::Receive('b'),
+        ];
+        let mut machine = Machine::new(&program);
+
+        assert_eq!(machine.step().unwrap(), Status::Running);
+        assert_eq!(machine.register('a'), 3);
+        assert_eq!(machine.step().unwrap(), Status::Sent(3));
+        assert_eq!(machine.outbox().len(), 1);
+        assert_eq!(machine.step().unwrap(), Status::Blocked);
+        assert_eq!(machine.program_counter(), 2);
+        assert_eq!(machine.step().unwrap(), Status::Blocked);
+
+        machine.push_input(9);
+        assert_eq!(machine.step().unwrap(), Status::Running);
+        assert_eq!(machine.register('b'), 9);
+        assert_eq!(machine.step().unwrap(), Status::Halted);
+        assert!(machine.is_halted());
+        assert_eq!(machine.steps(), 3);
+    }
+
+    #[test]
+    fn run_until_blocked_collects_output() {
+        let program = vec![
+            Instruction::Send(Value::Literal(1)),
+            Instruction::Send(Value::FromRegister('p')),
+            Instruction::Receive('a'),
+            Instruction::Add('a', Value::Literal(1)),
+        ];
+        let mut machine = Machine::with_id(&program, 7);
+
+        assert_eq!(machine.run_until_blocked().unwrap(), Status::Blocked);
+        assert_eq!(machine.take_output(), Some(1));
+        assert_eq!(machine.take_output(), Some(7));
+        assert_eq!(machine.take_output(), None);
+        assert_eq!(machine.messages_sent(), 2);
+
+        machine.push_input(4);
+        assert_eq!(machine.run_until_blocked().unwrap(), Status::Halted);
+        assert_eq!(machine.register('a'), 5);
+    }
+
+    #[test]
+    fn jumps_move_program_counter() {
+        let program = vec![
+            Instruction::JumpIfGreaterThanZero(Value::Literal(1), Value::Literal(2)),
+            Instruction::Set('a', Value::Literal(1)),
+            Instruction::JumpIfGreaterThanZero(Value::FromRegister('a'), Value::Literal(-1)),
+        ];
+        let mut machine = Machine::new(&program);
+
+        machine.step().unwrap();
+        assert_eq!(machine.program_counter(), 2);
+        assert_eq!(machine.current_instruction(), Some(&program[2]));
+        machine.step().unwrap();
+        assert_eq!(machine.program_counter(), 3);
+        assert!(machine.is_halted());
+    }
+
+    #[test]
+    fn skip_does_not_execute() {
+        let program = vec![Instruction::Set('a', Value::Literal(1))];
+        let mut machine = Machine::new(&program);
+
+        machine.skip();
+        assert!(machine.is_halted());
+        assert_eq!(machine.register('a'), 0);
+        assert_eq!(machine.steps(), 0);
+    }
+
+    fn run_with_mode(program: &[Instruction], mode: ArithmeticMode) -> Result<i64, RuntimeError> {
+        let mut machine = Machine::new(program);
+        machine.set_arithmetic_mode(mode);
+        machine.run_until_blocked()?;
+        Ok(machine.register('a'))
+    }
+
+    #[test]
+    fn multiply_overflow() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(i64::MAX / 2 + 1)),
+            Instruction::Multiply('a', Value::Literal(2)),
+        ];
+
+        let error = run_with_mode(&program, ArithmeticMode::Checked).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::Overflow);
+        assert_eq!(error.index, 1);
+        assert_eq!(error.registers.get(&'a'), Some(&(i64::MAX / 2 + 1)));
+
+        assert_eq!(
+            run_with_mode(&program, ArithmeticMode::Wrapping).unwrap(),
+            i64::MIN
+        );
+        assert_eq!(
+            run_with_mode(&program, ArithmeticMode::Saturating).unwrap(),
+            i64::MAX
+        );
+    }
+
+    #[test]
+    fn add_overflow() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(i64::MIN)),
+            Instruction::Add('a', Value::Literal(-1)),
+        ];
+
+        let error = run_with_mode(&program, ArithmeticMode::Checked).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::Overflow);
+        assert_eq!(
+            run_with_mode(&program, ArithmeticMode::Wrapping).unwrap(),
+            i64::MAX
+        );
+        assert_eq!(
+            run_with_mode(&program, ArithmeticMode::Saturating).unwrap(),
+            i64::MIN
+        );
+    }
+
+    #[test]
+    fn modulus_by_zero() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(5)),
+            Instruction::Modulus('a', Value::FromRegister('b')),
+        ];
+
+        let modes = [
+            ArithmeticMode::Wrapping,
+            ArithmeticMode::Saturating,
+            ArithmeticMode::Checked,
+        ];
+
+        for &mode in &modes {
+            let error = run_with_mode(&program, mode).unwrap_err();
+            assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+            assert_eq!(error.index, 1);
+        }
+    }
+
+    #[test]
+    fn modulus_of_minimum_value() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(i64::MIN)),
+            Instruction::Modulus('a', Value::Literal(-1)),
+        ];
+
+        assert_eq!(run_with_mode(&program, ArithmeticMode::Checked).unwrap(), 0);
+    }
+
+    #[test]
+    fn jump_before_start_of_program() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(1)),
+            Instruction::JumpIfGreaterThanZero(Value::FromRegister('a'), Value::Literal(-2)),
+        ];
+        let mut machine = Machine::new(&program);
+
+        let error = machine.run_until_blocked().unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::JumpOutOfRange);
+        assert_eq!(error.index, 1);
+        assert_eq!(machine.program_counter(), 1);
+    }
+
+    #[test]
+    fn huge_jump_halts() {
+        let program = vec![
+            Instruction::JumpIfGreaterThanZero(Value::Literal(1), Value::Literal(i64::MAX)),
+        ];
+        let mut machine = Machine::new(&program);
+
+        assert_eq!(machine.step().unwrap(), Status::Running);
+        assert!(machine.is_halted());
+
+        let program = vec![
+            Instruction::Set('a', Value::Literal(1)),
+            Instruction::JumpIfGreaterThanZero(Value::FromRegister('a'), Value::Literal(i64::MAX)),
+        ];
+        let mut machine = Machine::new(&program);
+
+        assert_eq!(machine.run_until_blocked().unwrap(), Status::Halted);
+        assert_eq!(machine.steps(), 2);
+    }
+
+    #[test]
+    fn error_message_lists_registers() {
+        let program = vec![
+            Instruction::Set('b', Value::Literal(2)),
+            Instruction::Set('a', Value::Literal(1)),
+            Instruction::Modulus('a', Value::Literal(0)),
+        ];
+
+        let error = run_with_mode(&program, ArithmeticMode::Checked).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Modulus by zero at instruction 2 (a = 1, b = 2)"
+        );
+    }
+}
@@ -1,5 +1,9 @@
 extern crate duet;
-use duet::{Instruction, Error, play, perform_duet};
+use duet::{duet_network, parse_program, play};
+use std::env;
+use std::error;
+use std::fs::File;
+use std::io::BufWriter;
 use std::process::exit;
 
 fn main() {
@@ -9,19 +13,42 @@ fn main() {
     }
 }
 
-fn run() -> Result<(), Error> {
-    let instructions = include_str!("puzzle_input.txt")
-        .lines()
-        .map(|i| i.parse::<Instruction>())
-        .collect::<Result<Vec<Instruction>, Error>>()?;
+fn run() -> Result<(), Box<dyn error::Error>> {
+    let instructions = match parse_program(include_str!("puzzle_input.txt")) {
+        Ok(instructions) => instructions,
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}\n", diagnostic);
+            }
+
+            return Err(format!("{} invalid lines in puzzle input", diagnostics.len()).into());
+        }
+    };
     
-    let sound = play(&instructions);
+    let sound = play(&instructions)?;
     match sound {
         Some(s) => println!("Frequency of first sound played is {} Hz.", s),
         None => println!("No sound is played.")
     };
 
-    perform_duet(&instructions);
+    // With --trace PREFIX, each program's trace is written to PREFIX-ID.jsonl.
+    let trace_prefix = env::args().skip_while(|arg| arg != "--trace").nth(1);
+
+    let mut network = duet_network(&instructions);
+    network.set_tracing(trace_prefix.is_some());
+    let report = network.run()?;
+    for program in &report.programs {
+        println!(
+            "Program {} sent {} messages.",
+            program.id, program.messages_sent
+        );
+
+        if let (Some(prefix), Some(trace)) = (trace_prefix.as_ref(), program.trace.as_ref()) {
+            let path = format!("{}-{}.jsonl", prefix, program.id);
+            trace.write(BufWriter::new(File::create(&path)?))?;
+            println!("Wrote {} steps to {}", trace.len(), path);
+        }
+    }
 
     Ok(())
 }
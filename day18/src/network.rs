@@ -0,0 +1,324 @@
+use super::{
+    ArithmeticMode, DuetReport, Instruction, Machine, ProgramReport, RuntimeError, RuntimeErrorKind,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Topology {
+    // Each program sends to the next one, and the last sends to the first.
+    Ring,
+    // Programs 0 and 1 talk to each other, as do 2 and 3, and so on. Anything
+    // sent by an unpaired last program is lost.
+    Pairs,
+    // Each program sends to every other program.
+    Broadcast,
+    // The destinations of each program's messages, indexed by sender.
+    Custom(Vec<Vec<usize>>),
+}
+
+impl Topology {
+    pub fn destinations(&self, sender: usize, program_count: usize) -> Vec<usize> {
+        match *self {
+            Topology::Ring => vec![(sender + 1) % program_count],
+            Topology::Pairs => {
+                let partner = sender ^ 1;
+                if partner < program_count {
+                    vec![partner]
+                } else {
+                    Vec::new()
+                }
+            }
+            Topology::Broadcast => (0..program_count).filter(|&n| n != sender).collect(),
+            Topology::Custom(ref destinations) => {
+                destinations.get(sender).cloned().unwrap_or_default()
+            }
+        }
+    }
+}
+
+pub struct Network<'a> {
+    programs: Vec<(i64, Machine<'a>)>,
+    topology: Topology,
+    arithmetic_mode: ArithmeticMode,
+    tracing: bool,
+}
+
+impl<'a> Network<'a> {
+    pub fn new(topology: Topology) -> Network<'a> {
+        Network {
+            programs: Vec::new(),
+            topology,
+            arithmetic_mode: ArithmeticMode::default(),
+            tracing: false,
+        }
+    }
+
+    // Applies to programs already in the network as well as those added later.
+    pub fn set_arithmetic_mode(&mut self, mode: ArithmeticMode) {
+        self.arithmetic_mode = mode;
+        for &mut (_, ref mut machine) in &mut self.programs {
+            machine.set_arithmetic_mode(mode);
+        }
+    }
+
+    // Whether to record a trace of each program, which is returned in its
+    // report. Like the arithmetic mode, applies to every program.
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+        for &mut (_, ref mut machine) in &mut self.programs {
+            if tracing {
+                machine.start_trace();
+            } else {
+                machine.take_trace();
+            }
+        }
+    }
+
+    // Adds a program whose register p starts out holding `id`, and returns its
+    // position in the topology.
+    pub fn add_program(&mut self, instructions: &'a [Instruction], id: i64) -> usize {
+        let mut machine = Machine::with_id(instructions, id);
+        machine.set_arithmetic_mode(self.arithmetic_mode);
+        if self.tracing {
+            machine.start_trace();
+        }
+        self.programs.push((id, machine));
+        self.programs.len() - 1
+    }
+
+    // Runs every program in turn on a single thread until none of them can
+    // make progress, either because they have terminated or because they are
+    // all waiting for messages that will never arrive. An error names the
+    // program that failed, which is also the one whose messages have nowhere
+    // to go if the topology is wrong for the number of programs.
+    pub fn run(mut self) -> Result<DuetReport, RuntimeError> {
+        let program_count = self.programs.len();
+        let destinations: Vec<Vec<usize>> = (0..program_count)
+            .map(|sender| self.topology.destinations(sender, program_count))
+            .collect();
+
+        for (sender, targets) in destinations.iter().enumerate() {
+            if let Some(&target) = targets.iter().find(|&&target| target >= program_count) {
+                let (id, ref machine) = self.programs[sender];
+                let error = RuntimeError::new(
+                    RuntimeErrorKind::NoSuchDestination(target),
+                    machine.program_counter(),
+                    &machine.registers(),
+                );
+                return Err(error.in_program(id));
+            }
+        }
+
+        loop {
+            let mut executed = 0;
+
+            for (sender, targets) in destinations.iter().enumerate() {
+                let (id, ref mut machine) = self.programs[sender];
+                let steps_before = machine.steps();
+                machine.run_until_blocked().map_err(|error| error.in_program(id))?;
+                executed += machine.steps() - steps_before;
+
+                while let Some(value) = self.programs[sender].1.take_output() {
+                    for &target in targets {
+                        self.programs[target].1.push_input(value);
+                    }
+                }
+            }
+
+            if executed == 0 {
+                break;
+            }
+        }
+
+        Ok(DuetReport {
+            programs: self.programs
+                .into_iter()
+                .map(|(id, mut machine)| ProgramReport {
+                    id,
+                    messages_sent: machine.messages_sent(),
+                    registers: machine.registers(),
+                    trace: machine.take_trace(),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Value;
+
+    fn receive_once() -> Vec<Instruction> {
+        vec![
+            Instruction::Send(Value::FromRegister('p')),
+            Instruction::Receive('a'),
+        ]
+    }
+
+    fn receive_twice() -> Vec<Instruction> {
+        vec![
+            Instruction::Send(Value::FromRegister('p')),
+            Instruction::Receive('a'),
+            Instruction::Receive('b'),
+        ]
+    }
+
+    #[test]
+    fn topology_destinations() {
+        assert_eq!(Topology::Ring.destinations(2, 3), vec![0]);
+        assert_eq!(Topology::Pairs.destinations(2, 4), vec![3]);
+        assert_eq!(Topology::Pairs.destinations(3, 4), vec![2]);
+        assert_eq!(Topology::Pairs.destinations(4, 5), Vec::<usize>::new());
+        assert_eq!(Topology::Broadcast.destinations(1, 3), vec![0, 2]);
+
+        let custom = Topology::Custom(vec![vec![1, 2], vec![]]);
+        assert_eq!(custom.destinations(0, 3), vec![1, 2]);
+        assert_eq!(custom.destinations(2, 3), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn ring_passes_messages_on() {
+        let program = receive_once();
+        let mut network = Network::new(Topology::Ring);
+        for id in 1..4 {
+            network.add_program(&program, id * 10);
+        }
+
+        let report = network.run().unwrap();
+        let received: Vec<i64> = report
+            .programs
+            .iter()
+            .map(|program| program.registers[&'a'])
+            .collect();
+
+        assert_eq!(received, vec![30, 10, 20]);
+    }
+
+    #[test]
+    fn pairs_only_talk_to_each_other() {
+        let program = receive_twice();
+        let mut network = Network::new(Topology::Pairs);
+        for id in 0..4 {
+            network.add_program(&program, id * 10);
+        }
+
+        let report = network.run().unwrap();
+        assert_eq!(report.programs[0].registers.get(&'a'), Some(&10));
+        assert_eq!(report.programs[1].registers.get(&'a'), Some(&0));
+        assert_eq!(report.programs[2].registers.get(&'a'), Some(&30));
+        assert_eq!(report.programs[3].registers.get(&'a'), Some(&20));
+        assert_eq!(report.programs[3].registers.get(&'b'), None);
+    }
+
+    #[test]
+    fn broadcast_reaches_every_other_program() {
+        let program = receive_twice();
+        let mut network = Network::new(Topology::Broadcast);
+        for id in 0..3 {
+            network.add_program(&program, id + 1);
+        }
+
+        let report = network.run().unwrap();
+        let received: Vec<(i64, i64)> = report
+            .programs
+            .iter()
+            .map(|program| (program.registers[&'a'], program.registers[&'b']))
+            .collect();
+
+        assert_eq!(received, vec![(2, 3), (1, 3), (1, 2)]);
+        assert!(report.programs.iter().all(|program| program.messages_sent == 1));
+    }
+
+    #[test]
+    fn custom_topology_with_different_programs() {
+        let sender = vec![
+            Instruction::Send(Value::Literal(7)),
+            Instruction::Send(Value::Literal(8)),
+        ];
+        let receiver = receive_twice();
+
+        let mut network = Network::new(Topology::Custom(vec![vec![1], vec![]]));
+        network.add_program(&sender, 0);
+        network.add_program(&receiver, 1);
+
+        let report = network.run().unwrap();
+        assert_eq!(report.messages_sent_by(0), Some(2));
+        assert_eq!(report.programs[1].registers.get(&'a'), Some(&7));
+        assert_eq!(report.programs[1].registers.get(&'b'), Some(&8));
+    }
+
+    #[test]
+    fn custom_topology_to_missing_program() {
+        let program = receive_twice();
+        let mut network = Network::new(Topology::Custom(vec![vec![], vec![0, 5]]));
+        network.add_program(&program, 3);
+        network.add_program(&program, 4);
+
+        let error = network.run().unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::NoSuchDestination(5));
+        assert_eq!(error.program, Some(4));
+        assert_eq!(
+            error.to_string(),
+            "Program 4: Sends to program 5, which is not in the network"
+        );
+    }
+
+    #[test]
+    fn empty_network() {
+        let report = Network::new(Topology::Ring).run().unwrap();
+        assert!(report.programs.is_empty());
+    }
+
+    #[test]
+    fn arithmetic_mode_applies_to_every_program() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(i64::MAX)),
+            Instruction::Add('a', Value::FromRegister('p')),
+        ];
+
+        let mut network = Network::new(Topology::Pairs);
+        network.add_program(&program, 1);
+        network.set_arithmetic_mode(ArithmeticMode::Saturating);
+        network.add_program(&program, 2);
+
+        let report = network.run().unwrap();
+        assert!(report.programs.iter().all(|program| program.registers[&'a'] == i64::MAX));
+    }
+
+    #[test]
+    fn trace_every_program() {
+        let program = receive_once();
+        let mut network = Network::new(Topology::Pairs);
+        network.add_program(&program, 4);
+        network.set_tracing(true);
+        network.add_program(&program, 7);
+
+        let report = network.run().unwrap();
+        for (program, received) in report.programs.iter().zip(&[7, 4]) {
+            let trace = program.trace.as_ref().unwrap();
+            assert_eq!(trace.initial_registers().get(&'p'), Some(&program.id));
+
+            let instructions: Vec<&str> =
+                trace.events().iter().map(|e| e.instruction.as_str()).collect();
+            assert_eq!(instructions, vec!["snd p", "rcv a"]);
+            assert_eq!(trace.registers_at(2).get(&'a'), Some(received));
+        }
+    }
+
+    #[test]
+    fn runtime_error_stops_the_network() {
+        let program = vec![Instruction::Modulus('a', Value::FromRegister('p'))];
+        let mut network = Network::new(Topology::Pairs);
+        network.add_program(&program, 1);
+        network.add_program(&program, 0);
+
+        let error = network.run().unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+        assert_eq!(error.program, Some(0));
+        assert_eq!(error.registers.get(&'p'), Some(&0));
+        assert_eq!(
+            error.to_string(),
+            "Program 0: Modulus by zero at instruction 0 (p = 0)"
+        );
+    }
+}
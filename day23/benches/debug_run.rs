@@ -0,0 +1,109 @@
+#[macro_use]
+extern crate criterion;
+extern crate conflagration;
+
+use conflagration::{Instruction, Processor};
+use criterion::Criterion;
+
+// The processor as it was before registers were compiled to slots, looking
+// every register up by name in a map, kept here as a baseline for
+// comparison. The debug run never uses the optimizer's macro-instructions.
+mod hashmap {
+    use conflagration::{Instruction, Register, Value};
+    use std::collections::HashMap;
+
+    pub struct Processor {
+        registers: HashMap<Register, i64>,
+        multiplications: usize,
+    }
+
+    impl Processor {
+        pub fn debug() -> Processor {
+            Processor {
+                registers: HashMap::new(),
+                multiplications: 0,
+            }
+        }
+
+        pub fn multiplication_count(&self) -> usize {
+            self.multiplications
+        }
+
+        pub fn execute(&mut self, instructions: &[Instruction]) {
+            let mut index: usize = 0;
+            while index < instructions.len() {
+                index = self.step(instructions, index);
+            }
+        }
+
+        fn step(&mut self, instructions: &[Instruction], index: usize) -> usize {
+            match instructions[index] {
+                Instruction::Set(register, ref value) => {
+                    let x = self.get_value(value);
+                    self.registers.insert(register, x);
+                }
+                Instruction::Subtract(register, ref value) => {
+                    let x = self.get_value(value);
+                    *self.registers.entry(register).or_insert(0) -= x;
+                }
+                Instruction::Multiply(register, ref value) => {
+                    self.multiplications += 1;
+                    let x = self.get_value(value);
+                    *self.registers.entry(register).or_insert(0) *= x;
+                }
+                Instruction::JumpIfNotZero(ref condition, ref value) => {
+                    if self.get_value(condition) != 0 {
+                        return ((index as i64) + self.get_value(value)) as usize;
+                    }
+                }
+                _ => unreachable!("the debug run has no macro-instructions"),
+            }
+
+            index + 1
+        }
+
+        fn get_value(&self, value: &Value) -> i64 {
+            match *value {
+                Value::FromRegister(r) => *self.registers.get(&r).unwrap_or(&0),
+                Value::Literal(n) => n,
+            }
+        }
+    }
+}
+
+fn puzzle_input() -> Vec<Instruction> {
+    include_str!("../src/puzzle_input.txt")
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect()
+}
+
+fn compare_implementations(c: &mut Criterion) {
+    let instructions = puzzle_input();
+
+    let mut processor = Processor::debug(&instructions);
+    processor.execute().unwrap();
+    let mut baseline = hashmap::Processor::debug();
+    baseline.execute(&instructions);
+    assert_eq!(processor.multiplication_count(), baseline.multiplication_count());
+
+    let mut group = c.benchmark_group("debug run");
+    group.bench_function("compiled", |b| {
+        b.iter(|| {
+            let mut processor = Processor::debug(&instructions);
+            processor.execute().unwrap();
+            processor.multiplication_count()
+        })
+    });
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            let mut processor = hashmap::Processor::debug();
+            processor.execute(&instructions);
+            processor.multiplication_count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, compare_implementations);
+criterion_main!(benches);
@@ -0,0 +1,316 @@
+extern crate conflagration;
+use conflagration::{Debugger, Instruction, Processor, Register, Stop};
+use conflagration::errors::Error;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::process::exit;
+
+const USAGE: &str = "Usage: debugger [--release] [FILE]
+Step through a coprocessor program, which defaults to the puzzle input.
+
+  --release  start with register a set to 1";
+
+const HELP: &str = "Commands:
+  s, step [N]        execute N instructions (default 1)
+  c, continue        run to the next breakpoint, watchpoint or the end
+  b, break [N]       set a breakpoint at instruction N, or list breakpoints
+  d, delete N        remove the breakpoint at instruction N
+  w, watch R         stop whenever register R changes
+  u, unwatch R       stop watching register R
+  r, registers       show every register
+  set R V            set register R to V
+  l, list            show the instructions around the current one
+  h, help            show this help
+  q, quit            exit the debugger
+An empty line repeats the previous command.";
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Command {
+    Step(usize),
+    Continue,
+    Break(Option<usize>),
+    Delete(usize),
+    Watch(Register),
+    Unwatch(Register),
+    Registers,
+    Set(Register, i64),
+    List,
+    Help,
+    Quit,
+}
+
+fn parse_register(word: Option<&str>) -> Result<Register, String> {
+    match word.map(|w| w.chars().collect::<Vec<char>>()) {
+        Some(ref chars) if chars.len() == 1 && chars[0].is_ascii_lowercase() => Ok(chars[0]),
+        Some(_) => Err("A register is a single lower case letter".to_string()),
+        None => Err("Missing register".to_string()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>) -> Result<T, String> {
+    match word {
+        Some(w) => w.parse().map_err(|_| format!("'{}' is not a valid number", w)),
+        None => Err("Missing number".to_string()),
+    }
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some(word) => word,
+        None => return Err("No command".to_string()),
+    };
+
+    let parsed = match command {
+        "s" | "step" => match words.next() {
+            Some(n) => Command::Step(parse_number(Some(n))?),
+            None => Command::Step(1),
+        },
+        "c" | "continue" => Command::Continue,
+        "b" | "break" => match words.next() {
+            Some(n) => Command::Break(Some(parse_number(Some(n))?)),
+            None => Command::Break(None),
+        },
+        "d" | "delete" => Command::Delete(parse_number(words.next())?),
+        "w" | "watch" => Command::Watch(parse_register(words.next())?),
+        "u" | "unwatch" => Command::Unwatch(parse_register(words.next())?),
+        "r" | "registers" => Command::Registers,
+        "set" => {
+            let register = parse_register(words.next())?;
+            Command::Set(register, parse_number(words.next())?)
+        }
+        "l" | "list" => Command::List,
+        "h" | "help" => Command::Help,
+        "q" | "quit" => Command::Quit,
+        _ => return Err(format!("Unknown command '{}'; type 'help' for a list", command)),
+    };
+
+    if words.next().is_some() {
+        return Err(format!("Too many arguments to '{}'", command));
+    }
+
+    Ok(parsed)
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("ERROR: {}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut release = false;
+    let mut path: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--release" => release = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+
+    let source = match path {
+        Some(path) => {
+            let mut source = String::new();
+            File::open(path)?.read_to_string(&mut source)?;
+            source
+        }
+        None => include_str!("../puzzle_input.txt").to_string(),
+    };
+
+    let instructions = source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse::<Instruction>())
+        .collect::<Result<Vec<Instruction>, Error>>()?;
+
+    let processor = if release {
+        Processor::release(&instructions)
+    } else {
+        Processor::debug(&instructions)
+    };
+
+    let mut debugger = Debugger::new(processor);
+    show_position(&debugger);
+
+    let stdin = io::stdin();
+    let mut previous: Option<Command> = None;
+    prompt()?;
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        let command = if line.trim().is_empty() {
+            previous.clone()
+        } else {
+            match parse_command(&line) {
+                Ok(command) => Some(command),
+                Err(message) => {
+                    println!("{}", message);
+                    None
+                }
+            }
+        };
+
+        if let Some(command) = command {
+            if command == Command::Quit {
+                return Ok(());
+            }
+
+            execute(&mut debugger, &command);
+            previous = Some(command);
+        }
+
+        prompt()?;
+    }
+
+    Ok(())
+}
+
+fn prompt() -> io::Result<()> {
+    print!("(dbg) ");
+    io::stdout().flush()
+}
+
+fn execute(debugger: &mut Debugger, command: &Command) {
+    match *command {
+        Command::Step(count) => {
+            let mut stop = Stop::Stepped;
+            for _ in 0..count {
+                stop = debugger.step();
+                if stop != Stop::Stepped {
+                    break;
+                }
+            }
+            report(debugger, stop);
+        }
+        Command::Continue => {
+            let stop = debugger.resume();
+            report(debugger, stop);
+        }
+        Command::Break(Some(index)) => {
+            if index >= debugger.instructions().len() {
+                println!("There is no instruction {}", index);
+            } else if debugger.add_breakpoint(index) {
+                println!("Breakpoint set at {}", index);
+            } else {
+                println!("There is already a breakpoint at {}", index);
+            }
+        }
+        Command::Break(None) => {
+            if debugger.breakpoints().is_empty() {
+                println!("No breakpoints");
+            }
+            for &index in debugger.breakpoints() {
+                println!("{:>4}: {}", index, debugger.instructions()[index]);
+            }
+        }
+        Command::Delete(index) => {
+            if !debugger.remove_breakpoint(index) {
+                println!("There is no breakpoint at {}", index);
+            }
+        }
+        Command::Watch(register) => {
+            debugger.add_watchpoint(register);
+        }
+        Command::Unwatch(register) => {
+            if !debugger.remove_watchpoint(register) {
+                println!("Register {} is not being watched", register);
+            }
+        }
+        Command::Registers => show_registers(debugger),
+        Command::Set(register, value) => debugger.processor_mut().set_register(register, value),
+        Command::List => list(debugger),
+        Command::Help => println!("{}", HELP),
+        Command::Quit => {}
+    }
+}
+
+fn report(debugger: &Debugger, stop: Stop) {
+    match stop {
+        Stop::Stepped => {}
+        Stop::Breakpoint(index) => println!("Breakpoint at {}", index),
+        Stop::Watchpoint { register, old, new } => {
+            println!("Register {} changed from {} to {}", register, old, new)
+        }
+        Stop::Error(error) => println!("Error: {}", error),
+        Stop::Halted => {
+            println!(
+                "Program finished after {} steps with {} multiplications",
+                debugger.steps(),
+                debugger.processor().multiplication_count()
+            );
+            show_registers(debugger);
+            return;
+        }
+    }
+
+    show_position(debugger);
+}
+
+fn show_position(debugger: &Debugger) {
+    if let Some(instruction) = debugger.current_instruction() {
+        println!("{:>4}: {}", debugger.index(), instruction);
+    }
+}
+
+fn show_registers(debugger: &Debugger) {
+    let registers: Vec<String> = debugger
+        .processor()
+        .registers()
+        .iter()
+        .map(|&(register, value)| format!("{} = {}", register, value))
+        .collect();
+
+    if registers.is_empty() {
+        println!("All registers are 0");
+    } else {
+        println!("{}", registers.join(", "));
+    }
+}
+
+fn list(debugger: &Debugger) {
+    let current = debugger.index();
+    let first = current.saturating_sub(5);
+    let last = (current + 6).min(debugger.instructions().len());
+
+    for index in first..last {
+        let marker = if index == current { "=>" } else { "  " };
+        let breakpoint = if debugger.breakpoints().contains(&index) { "*" } else { " " };
+        println!(
+            "{}{}{:>4}: {}",
+            marker, breakpoint, index, debugger.instructions()[index]
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse_command("s"), Ok(Command::Step(1)));
+        assert_eq!(parse_command("step 10"), Ok(Command::Step(10)));
+        assert_eq!(parse_command("b"), Ok(Command::Break(None)));
+        assert_eq!(parse_command("break 12"), Ok(Command::Break(Some(12))));
+        assert_eq!(parse_command("watch h"), Ok(Command::Watch('h')));
+        assert_eq!(parse_command("set a -3"), Ok(Command::Set('a', -3)));
+        assert_eq!(parse_command("  q  "), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn reject_bad_commands() {
+        assert!(parse_command("jump 3").is_err());
+        assert!(parse_command("b x").is_err());
+        assert!(parse_command("w ab").is_err());
+        assert!(parse_command("set a").is_err());
+        assert!(parse_command("c now").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+extern crate conflagration;
+use conflagration::{decompile, optimize, ControlFlowGraph, Instruction};
+use conflagration::errors::Error;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process::exit;
+
+const USAGE: &str = "Usage: decompile [--dot] [--optimize] [FILE]
+Write a coprocessor program, which defaults to the puzzle input, as
+pseudocode.
+
+  --dot       write the control-flow graph in Graphviz format instead
+  --optimize  run the peephole optimizer first";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("ERROR: {}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut dot = false;
+    let mut optimized = false;
+    let mut path: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            "--optimize" => optimized = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+
+    let source = match path {
+        Some(path) => {
+            let mut source = String::new();
+            File::open(path)?.read_to_string(&mut source)?;
+            source
+        }
+        None => include_str!("../puzzle_input.txt").to_string(),
+    };
+
+    let mut instructions = source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse::<Instruction>())
+        .collect::<Result<Vec<Instruction>, Error>>()?;
+
+    if optimized {
+        instructions = optimize(&instructions);
+    }
+
+    if dot {
+        print!("{}", ControlFlowGraph::new(&instructions).to_dot(&instructions));
+    } else {
+        print!("{}", decompile(&instructions));
+    }
+
+    Ok(())
+}
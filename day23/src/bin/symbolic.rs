@@ -0,0 +1,106 @@
+extern crate conflagration;
+use conflagration::errors::Error;
+use conflagration::{execute_symbolically, optimize, Expr, Instruction, Path};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process::exit;
+
+const USAGE: &str = "Usage: symbolic [--debug | --release] [--optimize] [FILE]
+Execute a coprocessor program, which defaults to the puzzle input,
+symbolically and write closed forms for the registers it finishes with.
+
+  --debug     start with a = 0 rather than leaving it unknown
+  --release   start with a = 1
+  --optimize  run the peephole optimizer first";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("ERROR: {}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut a = Expr::input('a');
+    let mut optimized = false;
+    let mut path: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--debug" => a = Expr::constant(0),
+            "--release" => a = Expr::constant(1),
+            "--optimize" => optimized = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+
+    let source = match path {
+        Some(path) => {
+            let mut source = String::new();
+            File::open(path)?.read_to_string(&mut source)?;
+            source
+        }
+        None => include_str!("../puzzle_input.txt").to_string(),
+    };
+
+    let mut instructions = source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse::<Instruction>())
+        .collect::<Result<Vec<Instruction>, Error>>()?;
+
+    if optimized {
+        instructions = optimize(&instructions);
+    }
+
+    let paths = execute_symbolically(&instructions, &[('a', a)])?;
+    for (n, path) in paths.iter().enumerate() {
+        if n > 0 {
+            println!();
+        }
+        print_path(path);
+    }
+
+    Ok(())
+}
+
+fn print_path(path: &Path) {
+    if path.conditions.is_empty() {
+        println!("Always:");
+    } else {
+        let conditions: Vec<String> = path
+            .conditions
+            .iter()
+            .map(|&(ref condition, nonzero)| {
+                format!("{} {} 0", condition, if nonzero { "!=" } else { "==" })
+            })
+            .collect();
+        println!("When {}:", conditions.join(" and "));
+    }
+
+    // Show the value of anything that only depends on constants, such as a
+    // count of primes, as well as its closed form.
+    let inputs = BTreeMap::new();
+    for (register, expr) in &path.registers {
+        match expr.evaluate(&inputs) {
+            Some(value) if expr.as_constant().is_none() => {
+                println!("  {} = {}\n      = {}", register, expr, value)
+            }
+            _ => println!("  {} = {}", register, expr),
+        }
+    }
+
+    if !path.assumptions.is_empty() {
+        println!("  assuming:");
+        for assumption in &path.assumptions {
+            println!("    {} >= 0", assumption);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use super::{DivisorSearch, Instruction};
+use register_vm::bytecode::{compile_register, Slot, Source};
+
+// A DivisorSearch with its registers resolved to slots.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Search {
+    pub flag: Slot,
+    pub divisor: Slot,
+    pub cofactor: Slot,
+    pub target: Slot,
+    pub scratch: Slot,
+}
+
+// An instruction lowered so that each form of its arguments has its own
+// opcode. Jumps stay relative to the instruction they come from.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Op {
+    SetRegister(Slot, Slot),
+    SetLiteral(Slot, i64),
+    SubtractRegister(Slot, Slot),
+    SubtractLiteral(Slot, i64),
+    MultiplyRegister(Slot, Slot),
+    MultiplyLiteral(Slot, i64),
+    Jump(i64),
+    JumpIfNotZero(Slot, i64),
+    // A jump by the value of a register, so only known when it runs.
+    JumpBy(Source, Slot),
+    Nop,
+    SubtractProduct(Slot, Source, Slot),
+    TestDivisor(Search),
+    TestComposite(Search, i64),
+}
+
+fn compile_search(search: &DivisorSearch) -> Search {
+    Search {
+        flag: compile_register(search.flag),
+        divisor: compile_register(search.divisor),
+        cofactor: compile_register(search.cofactor),
+        target: compile_register(search.target),
+        scratch: compile_register(search.scratch),
+    }
+}
+
+pub fn compile(instruction: &Instruction) -> Op {
+    match *instruction {
+        Instruction::Set(r, ref v) => match Source::compile(v) {
+            Source::Register(s) => Op::SetRegister(compile_register(r), s),
+            Source::Literal(n) => Op::SetLiteral(compile_register(r), n),
+        },
+        Instruction::Subtract(r, ref v) => match Source::compile(v) {
+            Source::Register(s) => Op::SubtractRegister(compile_register(r), s),
+            Source::Literal(n) => Op::SubtractLiteral(compile_register(r), n),
+        },
+        Instruction::Multiply(r, ref v) => match Source::compile(v) {
+            Source::Register(s) => Op::MultiplyRegister(compile_register(r), s),
+            Source::Literal(n) => Op::MultiplyLiteral(compile_register(r), n),
+        },
+        Instruction::JumpIfNotZero(ref condition, ref offset) => {
+            match (Source::compile(condition), Source::compile(offset)) {
+                (Source::Literal(0), _) => Op::Nop,
+                (Source::Literal(_), Source::Literal(n)) => Op::Jump(n),
+                (Source::Register(c), Source::Literal(n)) => Op::JumpIfNotZero(c, n),
+                (condition, Source::Register(offset)) => Op::JumpBy(condition, offset),
+            }
+        }
+        Instruction::SubtractProduct(x, ref v, c) => {
+            Op::SubtractProduct(compile_register(x), Source::compile(v), compile_register(c))
+        }
+        Instruction::TestDivisor(ref search) => Op::TestDivisor(compile_search(search)),
+        Instruction::TestComposite(ref search, start) => {
+            Op::TestComposite(compile_search(search), start)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_program(program: &str) -> Vec<Op> {
+        program
+            .lines()
+            .map(|line| compile(&line.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn lower_arguments() {
+        assert_eq!(
+            compile_program("set a b\nset a 3\nsub c d\nsub c -1\nmul h a\nmul h 2"),
+            vec![
+                Op::SetRegister(0, 1),
+                Op::SetLiteral(0, 3),
+                Op::SubtractRegister(2, 3),
+                Op::SubtractLiteral(2, -1),
+                Op::MultiplyRegister(7, 0),
+                Op::MultiplyLiteral(7, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn lower_jumps() {
+        assert_eq!(
+            compile_program("jnz 0 5\njnz 1 2\njnz a -1\njnz 1 b\njnz a b"),
+            vec![
+                Op::Nop,
+                Op::Jump(2),
+                Op::JumpIfNotZero(0, -1),
+                Op::JumpBy(Source::Literal(1), 1),
+                Op::JumpBy(Source::Register(0), 1),
+            ]
+        );
+    }
+}
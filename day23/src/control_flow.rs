@@ -0,0 +1,312 @@
+use super::{Instruction, Register, Value};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// Where control can go after an instruction: the index of another one, or
+// any index at or past the end of the program, which stops it.
+pub fn successors(instructions: &[Instruction], index: usize) -> Vec<usize> {
+    let end = instructions.len();
+    let relative = |offset: i64| match (index as i64).checked_add(offset) {
+        Some(target) if target >= 0 && target <= end as i64 => target as usize,
+        _ => end,
+    };
+
+    let mut next = match instructions[index] {
+        Instruction::JumpIfNotZero(ref condition, Value::Literal(offset)) => match *condition {
+            Value::Literal(0) => vec![index + 1],
+            Value::Literal(_) => vec![relative(offset)],
+            Value::FromRegister(_) => vec![index + 1, relative(offset)],
+        },
+        // A jump by a register could go anywhere.
+        Instruction::JumpIfNotZero(_, Value::FromRegister(_)) => (0..end + 1).collect(),
+        Instruction::SubtractProduct(_, _, _) => vec![index + 3],
+        Instruction::TestDivisor(_) => vec![relative(9), index + 1],
+        Instruction::TestComposite(_, _) => vec![relative(14), index + 1],
+        _ => vec![index + 1],
+    };
+
+    next.dedup();
+    next
+}
+
+// The registers an instruction reads, and those it always overwrites.
+fn uses_and_definitions(instruction: &Instruction) -> (Vec<Register>, Vec<Register>) {
+    let register = |value: &Value| match *value {
+        Value::FromRegister(r) => vec![r],
+        Value::Literal(_) => vec![],
+    };
+
+    match *instruction {
+        Instruction::Set(r, ref v) => (register(v), vec![r]),
+        Instruction::Subtract(r, ref v) | Instruction::Multiply(r, ref v) => {
+            let mut uses = register(v);
+            uses.push(r);
+            (uses, vec![r])
+        }
+        Instruction::JumpIfNotZero(ref x, ref y) => {
+            let mut uses = register(x);
+            uses.extend(register(y));
+            (uses, vec![])
+        }
+        Instruction::SubtractProduct(x, ref v, c) => {
+            let mut uses = register(v);
+            uses.extend(&[x, c]);
+            (uses, vec![x, c])
+        }
+        Instruction::TestDivisor(ref s) => (
+            vec![s.flag, s.divisor, s.cofactor, s.target],
+            vec![s.scratch],
+        ),
+        Instruction::TestComposite(ref s, _) => (
+            vec![s.flag, s.divisor, s.target],
+            vec![s.cofactor],
+        ),
+    }
+}
+
+// For each instruction, the registers whose values may still be read after
+// it has executed.
+pub fn live_registers(instructions: &[Instruction]) -> Vec<BTreeSet<Register>> {
+    let end = instructions.len();
+    let mut live_in: Vec<BTreeSet<Register>> = vec![BTreeSet::new(); end];
+    let mut live_out: Vec<BTreeSet<Register>> = vec![BTreeSet::new(); end];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for index in (0..end).rev() {
+            let out: BTreeSet<Register> = successors(instructions, index)
+                .into_iter()
+                .filter(|&next| next < end)
+                .flat_map(|next| live_in[next].iter().cloned())
+                .collect();
+
+            let (uses, definitions) = uses_and_definitions(&instructions[index]);
+            let mut input: BTreeSet<Register> = out.iter()
+                .cloned()
+                .filter(|r| !definitions.contains(r))
+                .collect();
+            input.extend(uses);
+
+            if input != live_in[index] || out != live_out[index] {
+                live_in[index] = input;
+                live_out[index] = out;
+                changed = true;
+            }
+        }
+    }
+
+    live_out
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Successor {
+    Block(usize),
+    Exit,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Edge {
+    pub to: Successor,
+    // False when control simply falls through to the next instruction.
+    pub jump: bool,
+}
+
+// The instructions start..end, which always run one after another.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ControlFlowGraph {
+    blocks: Vec<BasicBlock>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(instructions: &[Instruction]) -> ControlFlowGraph {
+        let end = instructions.len();
+        let mut leaders: BTreeSet<usize> = BTreeSet::new();
+        leaders.insert(0);
+
+        for index in 0..end {
+            let next = successors(instructions, index);
+            if next != [index + 1] {
+                leaders.insert(index + 1);
+                leaders.extend(next);
+            }
+        }
+
+        let leaders: Vec<usize> = leaders.into_iter().filter(|&l| l < end).collect();
+        let block_of = |index: usize| match leaders.binary_search(&index) {
+            Ok(block) => Successor::Block(block),
+            Err(_) => Successor::Exit,
+        };
+
+        let blocks = leaders
+            .iter()
+            .enumerate()
+            .map(|(n, &start)| {
+                let block_end = leaders.get(n + 1).cloned().unwrap_or(end);
+                let last = block_end - 1;
+                let edges = successors(instructions, last)
+                    .into_iter()
+                    .map(|next| Edge {
+                        to: if next < end { block_of(next) } else { Successor::Exit },
+                        jump: next != last + 1,
+                    })
+                    .collect();
+
+                BasicBlock {
+                    start,
+                    end: block_end,
+                    edges,
+                }
+            })
+            .collect();
+
+        ControlFlowGraph { blocks }
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block_containing(&self, index: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.start <= index && index < block.end)
+    }
+
+    // Edges that go back to the same or an earlier block, each of which
+    // closes a loop. Returned as (from, to) block numbers.
+    pub fn back_edges(&self) -> Vec<(usize, usize)> {
+        let mut back_edges = Vec::new();
+        for (from, block) in self.blocks.iter().enumerate() {
+            for edge in &block.edges {
+                if let Successor::Block(to) = edge.to {
+                    if to <= from {
+                        back_edges.push((from, to));
+                    }
+                }
+            }
+        }
+
+        back_edges
+    }
+
+    // The graph in Graphviz format, with each block labelled with its code.
+    pub fn to_dot(&self, instructions: &[Instruction]) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        writeln!(dot, "    exit [shape=oval];").unwrap();
+
+        for (n, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for (index, instruction) in instructions.iter().enumerate().take(block.end).skip(block.start) {
+                write!(label, "{}: {}\\l", index, instruction).unwrap();
+            }
+            writeln!(dot, "    b{} [label=\"{}\"];", n, label).unwrap();
+        }
+
+        for (n, block) in self.blocks.iter().enumerate() {
+            for edge in &block.edges {
+                let to = match edge.to {
+                    Successor::Block(to) => format!("b{}", to),
+                    Successor::Exit => "exit".to_string(),
+                };
+                let style = if edge.jump { " [label=\"jump\"]" } else { "" };
+                writeln!(dot, "    b{} -> {}{};", n, to, style).unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(program: &str) -> Vec<Instruction> {
+        program.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn blocks_and_edges() {
+        let program = parse("set a 3\nsub a 1\njnz a -1\njnz 1 2\nset b 1\nset c 1");
+        let graph = ControlFlowGraph::new(&program);
+        let blocks = graph.blocks();
+
+        let spans: Vec<(usize, usize)> = blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(spans, vec![(0, 1), (1, 3), (3, 4), (4, 5), (5, 6)]);
+
+        assert_eq!(
+            blocks[1].edges,
+            vec![
+                Edge {
+                    to: Successor::Block(2),
+                    jump: false,
+                },
+                Edge {
+                    to: Successor::Block(1),
+                    jump: true,
+                },
+            ]
+        );
+        assert_eq!(
+            blocks[2].edges,
+            vec![Edge {
+                to: Successor::Block(4),
+                jump: true,
+            }]
+        );
+        assert_eq!(
+            blocks[4].edges,
+            vec![Edge {
+                to: Successor::Exit,
+                jump: false,
+            }]
+        );
+        assert_eq!(graph.back_edges(), vec![(1, 1)]);
+        assert_eq!(graph.block_containing(2), Some(1));
+    }
+
+    #[test]
+    fn jumps_out_of_the_program_exit() {
+        let program = parse("jnz a -5\njnz a 10");
+        assert_eq!(successors(&program, 0), vec![1, 2]);
+        assert_eq!(successors(&program, 1), vec![2]);
+
+        let program = parse("set a 1\njnz a 9223372036854775807");
+        assert_eq!(successors(&program, 1), vec![2]);
+    }
+
+    #[test]
+    fn liveness() {
+        let program = parse("set g a\nsub g b\njnz g 2\nset c g\nset g c");
+        let live = live_registers(&program);
+
+        assert!(live[1].contains(&'g'));
+        assert!(live[2].contains(&'g'));
+        assert!(live[3].contains(&'c'));
+        assert!(!live[3].contains(&'g'));
+        assert!(live[4].is_empty());
+    }
+
+    #[test]
+    fn dot_output() {
+        let program = parse("sub a 1\njnz a -1");
+        let dot = ControlFlowGraph::new(&program).to_dot(&program);
+
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("b0 [label=\"0: sub a 1\\l1: jnz a -1\\l\"];"));
+        assert!(dot.contains("b0 -> exit;"));
+        assert!(dot.contains("b0 -> b0 [label=\"jump\"];"));
+    }
+}
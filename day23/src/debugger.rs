@@ -0,0 +1,247 @@
+use super::{Instruction, Processor, Register};
+use errors::RuntimeError;
+use std::collections::BTreeSet;
+
+// Why the debugger handed control back.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint {
+        register: Register,
+        old: i64,
+        new: i64,
+    },
+    Halted,
+    // The instruction could not be executed, and is still the next one.
+    Error(RuntimeError),
+}
+
+pub struct Debugger<'a> {
+    processor: Processor<'a>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<Register>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(processor: Processor<'a>) -> Debugger<'a> {
+        Debugger {
+            processor,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn processor(&self) -> &Processor<'a> {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut Processor<'a> {
+        &mut self.processor
+    }
+
+    pub fn instructions(&self) -> &'a [Instruction] {
+        self.processor.instructions()
+    }
+
+    // The index of the next instruction to be executed.
+    pub fn index(&self) -> usize {
+        self.processor.program_counter()
+    }
+
+    pub fn current_instruction(&self) -> Option<&'a Instruction> {
+        self.processor.current_instruction()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.processor.is_halted()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.processor.steps()
+    }
+
+    pub fn add_breakpoint(&mut self, index: usize) -> bool {
+        self.breakpoints.insert(index)
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> bool {
+        self.breakpoints.remove(&index)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, register: Register) -> bool {
+        self.watchpoints.insert(register)
+    }
+
+    pub fn remove_watchpoint(&mut self, register: Register) -> bool {
+        self.watchpoints.remove(&register)
+    }
+
+    pub fn watchpoints(&self) -> &BTreeSet<Register> {
+        &self.watchpoints
+    }
+
+    // Executes one instruction, reporting a watched register that it changed.
+    pub fn step(&mut self) -> Stop {
+        if self.is_halted() {
+            return Stop::Halted;
+        }
+
+        let before: Vec<(Register, i64)> = self.watchpoints
+            .iter()
+            .map(|&r| (r, self.processor.get_register(r)))
+            .collect();
+
+        if let Err(error) = self.processor.step() {
+            return Stop::Error(error);
+        }
+
+        for (register, old) in before {
+            let new = self.processor.get_register(register);
+            if new != old {
+                return Stop::Watchpoint { register, old, new };
+            }
+        }
+
+        if self.is_halted() {
+            Stop::Halted
+        } else {
+            Stop::Stepped
+        }
+    }
+
+    // Runs until the program halts, a watched register changes, or execution
+    // reaches a breakpoint. At least one instruction is always executed, so
+    // continuing from a breakpoint does not stop at it again straight away.
+    pub fn resume(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::Stepped => {}
+                stop => return stop,
+            }
+
+            let index = self.index();
+            if self.breakpoints.contains(&index) {
+                return Stop::Breakpoint(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Value;
+    use errors::RuntimeErrorKind;
+
+    // Counts b down from 3 to 0, multiplying c by 2 each time.
+    fn countdown() -> Vec<Instruction> {
+        vec![
+            Instruction::Set('b', Value::Literal(3)),
+            Instruction::Set('c', Value::Literal(1)),
+            Instruction::Multiply('c', Value::Literal(2)),
+            Instruction::Subtract('b', Value::Literal(1)),
+            Instruction::JumpIfNotZero(Value::FromRegister('b'), Value::Literal(-2)),
+        ]
+    }
+
+    #[test]
+    fn single_step() {
+        let program = countdown();
+        let mut debugger = Debugger::new(Processor::debug(&program));
+
+        assert_eq!(debugger.step(), Stop::Stepped);
+        assert_eq!(debugger.index(), 1);
+        assert_eq!(debugger.processor().get_register('b'), 3);
+        assert_eq!(debugger.current_instruction(), Some(&program[1]));
+    }
+
+    #[test]
+    fn breakpoints() {
+        let program = countdown();
+        let mut debugger = Debugger::new(Processor::debug(&program));
+        debugger.add_breakpoint(3);
+
+        assert_eq!(debugger.resume(), Stop::Breakpoint(3));
+        assert_eq!(debugger.processor().get_register('c'), 2);
+        assert_eq!(debugger.resume(), Stop::Breakpoint(3));
+        assert_eq!(debugger.processor().get_register('c'), 4);
+
+        assert!(debugger.remove_breakpoint(3));
+        assert_eq!(debugger.resume(), Stop::Halted);
+        assert_eq!(debugger.processor().get_register('c'), 8);
+        assert_eq!(debugger.steps(), 11);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let program = countdown();
+        let mut debugger = Debugger::new(Processor::debug(&program));
+        debugger.add_watchpoint('b');
+
+        assert_eq!(
+            debugger.resume(),
+            Stop::Watchpoint {
+                register: 'b',
+                old: 0,
+                new: 3,
+            }
+        );
+        assert_eq!(
+            debugger.resume(),
+            Stop::Watchpoint {
+                register: 'b',
+                old: 3,
+                new: 2,
+            }
+        );
+        assert_eq!(debugger.index(), 4);
+    }
+
+    #[test]
+    fn stepping_after_halt() {
+        let program = vec![Instruction::Set('a', Value::Literal(1))];
+        let mut debugger = Debugger::new(Processor::debug(&program));
+
+        assert_eq!(debugger.step(), Stop::Halted);
+        assert_eq!(debugger.step(), Stop::Halted);
+        assert_eq!(debugger.steps(), 1);
+    }
+
+    #[test]
+    fn overflow_stops_on_the_failing_instruction() {
+        let program = vec![
+            Instruction::Set('a', Value::Literal(i64::MIN)),
+            Instruction::Subtract('a', Value::Literal(1)),
+        ];
+        let mut debugger = Debugger::new(Processor::debug(&program));
+
+        let error = RuntimeError {
+            kind: RuntimeErrorKind::Overflow,
+            index: 1,
+        };
+        assert_eq!(debugger.resume(), Stop::Error(error));
+        assert_eq!(debugger.step(), Stop::Error(error));
+        assert_eq!(debugger.index(), 1);
+        assert_eq!(debugger.steps(), 1);
+    }
+
+    #[test]
+    fn jumps_out_of_the_program_halt() {
+        for &offset in &[i64::MAX, -2, i64::MIN] {
+            let program = vec![
+                Instruction::Set('a', Value::Literal(1)),
+                Instruction::JumpIfNotZero(Value::FromRegister('a'), Value::Literal(offset)),
+            ];
+            let mut debugger = Debugger::new(Processor::debug(&program));
+
+            assert_eq!(debugger.resume(), Stop::Halted);
+            assert!(debugger.is_halted());
+            assert_eq!(debugger.steps(), 2);
+        }
+    }
+}
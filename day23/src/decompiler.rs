@@ -0,0 +1,456 @@
+use super::control_flow::{live_registers, successors};
+use super::{DivisorSearch, Instruction, Register, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+// A jnz condition, which jumps when `left != right`.
+#[derive(Debug, Clone)]
+enum Condition {
+    Always,
+    Never,
+    Compare(String, String),
+}
+
+impl Condition {
+    fn from_value(value: &Value) -> Condition {
+        match *value {
+            Value::Literal(0) => Condition::Never,
+            Value::Literal(_) => Condition::Always,
+            Value::FromRegister(r) => Condition::Compare(r.to_string(), "0".to_string()),
+        }
+    }
+
+    fn jumps(&self) -> String {
+        match *self {
+            Condition::Compare(ref left, ref right) => format!("{} != {}", left, right),
+            Condition::Always => "true".to_string(),
+            Condition::Never => "false".to_string(),
+        }
+    }
+
+    fn falls_through(&self) -> String {
+        match *self {
+            Condition::Compare(ref left, ref right) => format!("{} == {}", left, right),
+            Condition::Always => "false".to_string(),
+            Condition::Never => "true".to_string(),
+        }
+    }
+}
+
+// The innermost loop around the code being written out, so that jumps to
+// its start or just past its end can become continue and break.
+#[derive(Debug, Clone, Copy)]
+struct Loop {
+    header: usize,
+    exit: usize,
+}
+
+struct Decompiler<'a> {
+    instructions: &'a [Instruction],
+    // Maps the first instruction that computes a jnz condition in a scratch
+    // register to the index of that jnz and the condition it tests.
+    conditions: BTreeMap<usize, (usize, Condition)>,
+    output: String,
+}
+
+// Turns a program into structured pseudocode, recognising loops that end in
+// a backward jnz and if statements that skip forward over a block of code.
+// Anything that does not fit is left as a goto.
+pub fn decompile(instructions: &[Instruction]) -> String {
+    let mut decompiler = Decompiler {
+        instructions,
+        conditions: fold_conditions(instructions),
+        output: String::new(),
+    };
+
+    decompiler.region(0, instructions.len(), None, 0);
+    decompiler.output
+}
+
+fn jump_target(instructions: &[Instruction], index: usize) -> Option<usize> {
+    match instructions[index] {
+        Instruction::JumpIfNotZero(_, Value::Literal(offset)) => {
+            match (index as i64).checked_add(offset) {
+                Some(target) if target >= 0 && target <= instructions.len() as i64 => {
+                    Some(target as usize)
+                }
+                _ => Some(instructions.len()),
+            }
+        }
+        _ => None,
+    }
+}
+
+// Finds sequences like
+//
+// set g d
+// mul g e
+// sub g b
+// jnz g 2
+//
+// where g is not needed afterwards, so the jump can be written as
+// `if d * e != b`.
+fn fold_conditions(instructions: &[Instruction]) -> BTreeMap<usize, (usize, Condition)> {
+    let live = live_registers(instructions);
+    let mut targets: BTreeSet<usize> = BTreeSet::new();
+    for index in 0..instructions.len() {
+        let next = successors(instructions, index);
+        if next != [index + 1] {
+            targets.extend(next);
+        }
+    }
+
+    let mut conditions = BTreeMap::new();
+    for (jump, instruction) in instructions.iter().enumerate() {
+        let scratch = match *instruction {
+            Instruction::JumpIfNotZero(Value::FromRegister(r), _) if !live[jump].contains(&r) => r,
+            _ => continue,
+        };
+
+        let mut start = jump;
+        while start > 0 && !targets.contains(&start) {
+            start -= 1;
+            match instructions[start] {
+                Instruction::Subtract(r, ref v) | Instruction::Multiply(r, ref v)
+                    if r == scratch && *v != Value::FromRegister(r) => {}
+                Instruction::Set(r, ref v) if r == scratch && *v != Value::FromRegister(r) => {
+                    conditions.insert(start, (jump, build_condition(&instructions[start..jump])));
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    conditions
+}
+
+fn build_condition(instructions: &[Instruction]) -> Condition {
+    let mut left = String::new();
+    let mut right: Option<String> = None;
+
+    for instruction in instructions {
+        match *instruction {
+            Instruction::Set(_, ref v) => left = v.to_string(),
+            Instruction::Subtract(_, ref v) => {
+                if let Some(previous) = right.take() {
+                    left = format!("{} - {}", left, previous);
+                }
+                right = Some(v.to_string());
+            }
+            Instruction::Multiply(_, ref v) => {
+                left = match right.take() {
+                    Some(previous) => format!("({} - {}) * {}", left, previous, v),
+                    None => format!("{} * {}", left, v),
+                };
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Condition::Compare(left, right.unwrap_or_else(|| "0".to_string()))
+}
+
+impl<'a> Decompiler<'a> {
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    // The last backward jnz in start..end that goes to `header`.
+    fn latch(&self, header: usize, end: usize) -> Option<usize> {
+        (header..end).rev().find(|&index| {
+            jump_target(self.instructions, index) == Some(header)
+                && match self.instructions[index] {
+                    Instruction::JumpIfNotZero(ref condition, _) => {
+                        *condition != Value::Literal(0)
+                    }
+                    _ => false,
+                }
+        })
+    }
+
+    // The jnz at `index`, and where the code that computes its condition
+    // starts.
+    fn condition_ending_at(&self, index: usize, start: usize) -> (usize, Condition) {
+        for (&first, &(jump, ref condition)) in self.conditions.range(start..index + 1) {
+            if jump == index {
+                return (first, condition.clone());
+            }
+        }
+
+        match self.instructions[index] {
+            Instruction::JumpIfNotZero(ref condition, _) => (index, Condition::from_value(condition)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn goto(&mut self, target: usize, enclosing: Option<Loop>, depth: usize) {
+        let text = match enclosing {
+            Some(l) if target == l.header => "continue;".to_string(),
+            Some(l) if target == l.exit => "break;".to_string(),
+            _ if target >= self.instructions.len() => "halt;".to_string(),
+            _ => format!("goto {};", target),
+        };
+        self.line(depth, &text);
+    }
+
+    fn region(&mut self, start: usize, end: usize, enclosing: Option<Loop>, depth: usize) {
+        let mut index = start;
+
+        while index < end {
+            if let Some(next) = self.macro_instruction(index, depth) {
+                index = next;
+                continue;
+            }
+
+            if let Some(latch) = self.latch(index, end) {
+                let (condition_start, condition) = self.condition_ending_at(latch, index);
+                let inner = Some(Loop {
+                    header: index,
+                    exit: latch + 1,
+                });
+
+                match condition {
+                    Condition::Always => {
+                        self.line(depth, "loop {");
+                        self.region(index, latch, inner, depth + 1);
+                        self.line(depth, "}");
+                    }
+                    _ => {
+                        self.line(depth, "do {");
+                        self.region(index, condition_start, inner, depth + 1);
+                        self.line(depth, &format!("}} while {};", condition.jumps()));
+                    }
+                }
+
+                index = latch + 1;
+                continue;
+            }
+
+            let (jump, condition) = match self.conditions.get(&index) {
+                Some(&(jump, ref condition)) if jump < end => (jump, condition.clone()),
+                _ => match self.instructions[index] {
+                    Instruction::JumpIfNotZero(ref condition, _) => {
+                        (index, Condition::from_value(condition))
+                    }
+                    _ => {
+                        let statement = statement(&self.instructions[index]);
+                        self.line(depth, &statement);
+                        index += 1;
+                        continue;
+                    }
+                },
+            };
+
+            index = self.jump(jump, condition, end, enclosing, depth);
+        }
+    }
+
+    // Writes out the jnz at `jump`, returning where to carry on from.
+    fn jump(
+        &mut self,
+        jump: usize,
+        condition: Condition,
+        end: usize,
+        enclosing: Option<Loop>,
+        depth: usize,
+    ) -> usize {
+        let target = match jump_target(self.instructions, jump) {
+            Some(target) => target,
+            None => {
+                let offset = match self.instructions[jump] {
+                    Instruction::JumpIfNotZero(_, ref offset) => offset.to_string(),
+                    _ => unreachable!(),
+                };
+                self.line(depth, &format!("if {} {{", condition.jumps()));
+                self.line(depth + 1, &format!("goto {} + {};", jump, offset));
+                self.line(depth, "}");
+                return jump + 1;
+            }
+        };
+
+        match condition {
+            Condition::Never => return jump + 1,
+            Condition::Always => {
+                self.goto(target, enclosing, depth);
+                return jump + 1;
+            }
+            Condition::Compare(_, _) => {}
+        }
+
+        if target > jump + 1 && target <= end {
+            // jnz x 2 followed by jnz 1 n is a jump taken when x is zero.
+            let skip = match self.instructions.get(jump + 1) {
+                Some(&Instruction::JumpIfNotZero(Value::Literal(c), _)) if c != 0 && target == jump + 2 => {
+                    jump_target(self.instructions, jump + 1)
+                }
+                _ => None,
+            };
+
+            match skip {
+                Some(skip) if skip > jump + 2 && skip <= end => {
+                    self.line(depth, &format!("if {} {{", condition.jumps()));
+                    self.region(jump + 2, skip, enclosing, depth + 1);
+                    self.line(depth, "}");
+                    skip
+                }
+                Some(skip) => {
+                    self.line(depth, &format!("if {} {{", condition.falls_through()));
+                    self.goto(skip, enclosing, depth + 1);
+                    self.line(depth, "}");
+                    jump + 2
+                }
+                None => {
+                    self.line(depth, &format!("if {} {{", condition.falls_through()));
+                    self.region(jump + 1, target, enclosing, depth + 1);
+                    self.line(depth, "}");
+                    target
+                }
+            }
+        } else {
+            self.line(depth, &format!("if {} {{", condition.jumps()));
+            self.goto(target, enclosing, depth + 1);
+            self.line(depth, "}");
+            jump + 1
+        }
+    }
+
+    // Writes out an optimizer macro-instruction as the loops it replaced.
+    fn macro_instruction(&mut self, index: usize, depth: usize) -> Option<usize> {
+        match self.instructions[index] {
+            Instruction::SubtractProduct(x, ref v, c) => {
+                self.line(depth, &format!("{} -= {} * {};", x, v, c));
+                self.line(depth, &format!("{} = 0;", c));
+                Some(index + 3)
+            }
+            Instruction::TestDivisor(ref search) => {
+                self.divisor_loop(search, &search.cofactor.to_string(), depth);
+                Some(index + 9)
+            }
+            Instruction::TestComposite(ref search, start) => {
+                let d = search.divisor;
+                self.line(depth, &format!("for {} in {}..{} {{", d, d, search.target));
+                self.divisor_loop(search, &start.to_string(), depth + 1);
+                self.line(depth, "}");
+                Some(index + 14)
+            }
+            _ => None,
+        }
+    }
+
+    fn divisor_loop(&mut self, search: &DivisorSearch, from: &str, depth: usize) {
+        let (d, e, b) = (search.divisor, search.cofactor, search.target);
+        self.line(depth, &format!("for {} in {}..{} {{", e, from, b));
+        self.line(depth + 1, &format!("if {} * {} == {} {{", d, e, b));
+        self.line(depth + 2, &format!("{} = 0;", search.flag));
+        self.line(depth + 1, "}");
+        self.line(depth, "}");
+    }
+}
+
+fn statement(instruction: &Instruction) -> String {
+    let register = |r: Register| r.to_string();
+
+    match *instruction {
+        Instruction::Set(r, ref v) => format!("{} = {};", register(r), v),
+        Instruction::Subtract(r, Value::Literal(n)) if n < 0 && n != i64::MIN => {
+            format!("{} += {};", register(r), -n)
+        }
+        Instruction::Subtract(r, ref v) => format!("{} -= {};", register(r), v),
+        Instruction::Multiply(r, ref v) => format!("{} *= {};", register(r), v),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::optimize;
+
+    fn parse(program: &str) -> Vec<Instruction> {
+        program.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn decompile_puzzle_input() {
+        let program = parse(include_str!("puzzle_input.txt"));
+        let expected = "\
+b = 79;
+c = b;
+if a != 0 {
+    b *= 100;
+    b += 100000;
+    c = b;
+    c += 17000;
+}
+loop {
+    f = 1;
+    d = 2;
+    do {
+        e = 2;
+        do {
+            if d * e == b {
+                f = 0;
+            }
+            e += 1;
+        } while e != b;
+        d += 1;
+    } while d != b;
+    if f == 0 {
+        h += 1;
+    }
+    if b == c {
+        break;
+    }
+    b += 17;
+}
+";
+
+        assert_eq!(decompile(&program), expected);
+    }
+
+    #[test]
+    fn decompile_optimized_puzzle_input() {
+        let program = optimize(&parse(include_str!("puzzle_input.txt")));
+        let decompiled = decompile(&program);
+
+        assert!(decompiled.contains(
+            "    for d in d..b {
+        for e in 2..b {
+            if d * e == b {
+                f = 0;
+            }
+        }
+    }
+    if f == 0 {"
+        ));
+    }
+
+    #[test]
+    fn keep_conditions_whose_register_is_used_later() {
+        let program = parse("set g a\nsub g 3\njnz g 2\nset b 1\nset c g");
+        assert_eq!(
+            decompile(&program),
+            "g = a;\ng -= 3;\nif g == 0 {\n    b = 1;\n}\nc = g;\n"
+        );
+    }
+
+    #[test]
+    fn unstructured_jumps() {
+        let program = parse("jnz a 3\nset b 1\nset c 1\njnz d -2\njnz b c");
+        assert_eq!(
+            decompile(&program),
+            "if a == 0 {\n    b = 1;\n    c = 1;\n}\nif d != 0 {\n    goto 1;\n}\n\
+             if b != 0 {\n    goto 4 + c;\n}\n"
+        );
+    }
+
+    #[test]
+    fn huge_jumps_leave_the_program() {
+        let program = parse("set a 1\njnz a 9223372036854775807");
+        assert_eq!(decompile(&program), "if 1 != 0 {\n    halt;\n}\n");
+    }
+}
@@ -1,52 +1,42 @@
+pub use register_vm::{Error, ErrorKind};
+use register_vm::Halted;
 use std::error;
 use std::fmt;
 
-#[derive(Debug, Eq, PartialEq)]
-pub enum ErrorKind {
-    UnrecognizedInstruction,
-    MissingArgument,
-    WrongArgumentType,
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum RuntimeErrorKind {
+    Overflow,
+    // Stopped for exceeding its execution budget.
+    Halted(Halted),
 }
 
-#[derive(Debug)]
-pub struct Error {
-    pub kind: ErrorKind,
-}
-
-impl Error {
-    pub fn missing_argument() -> Error {
-        Error {
-            kind: ErrorKind::MissingArgument,
-        }
-    }
-
-    pub fn unrecognized_instruction() -> Error {
-        Error {
-            kind: ErrorKind::UnrecognizedInstruction,
-        }
+impl From<Halted> for RuntimeErrorKind {
+    fn from(halted: Halted) -> RuntimeErrorKind {
+        RuntimeErrorKind::Halted(halted)
     }
+}
 
-    pub fn wrong_type() -> Error {
-        Error {
-            kind: ErrorKind::WrongArgumentType,
-        }
-    }
+// A program that stopped before the end. `index` is the instruction it was
+// about to execute, which is left unexecuted.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub index: usize,
 }
 
-impl fmt::Display for Error {
+impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
-            ErrorKind::UnrecognizedInstruction => write!(f, "Unrecognized instruction"),
-            ErrorKind::MissingArgument => write!(f, "Missing argument"),
-            ErrorKind::WrongArgumentType => {
-                write!(f, "Literal value supplied where register required")
-            }
+            RuntimeErrorKind::Overflow => write!(f, "Arithmetic overflow")?,
+            RuntimeErrorKind::Halted(halted) => write!(f, "{}", halted)?,
         }
+
+        write!(f, " at instruction {}", self.index)
     }
 }
 
-impl error::Error for Error {
+impl error::Error for RuntimeError {
     fn description(&self) -> &str {
-        "Invalid instruction"
+        "Runtime error"
     }
 }
@@ -0,0 +1,812 @@
+use register_vm::Register;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+// A variable bound by a sum or an existential condition, or standing for a
+// register's value while a loop is being summarized. Variables are told
+// apart by id; the name is only for display.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
+pub struct Variable {
+    pub id: usize,
+    pub name: String,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
+pub enum Atom {
+    // The value a register held when the program started.
+    Input(Register),
+    Variable(Variable),
+    // 1 if the condition holds, otherwise 0.
+    Indicator(Box<Condition>),
+    Sum(Box<Sum>),
+}
+
+// The sum of `body` over every value of `variable` from `from` to `to`
+// inclusive.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
+pub struct Sum {
+    pub variable: Variable,
+    pub from: Expr,
+    pub to: Expr,
+    pub body: Expr,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
+pub enum Condition {
+    Zero(Expr),
+    // Whether the condition holds for any value of `variable` from `from` to
+    // `to` inclusive.
+    Exists {
+        variable: Variable,
+        from: Expr,
+        to: Expr,
+        condition: Box<Condition>,
+    },
+}
+
+// A polynomial with integer coefficients over atoms. Each monomial is kept
+// sorted, with zero coefficients dropped, so that equal polynomials compare
+// equal.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Default)]
+pub struct Expr {
+    terms: BTreeMap<Vec<Atom>, i64>,
+}
+
+impl Atom {
+    fn is_indicator(&self) -> bool {
+        matches!(*self, Atom::Indicator(_))
+    }
+
+    fn mentions_where(&self, test: &dyn Fn(usize) -> bool) -> bool {
+        match *self {
+            Atom::Input(_) => false,
+            Atom::Variable(ref v) => test(v.id),
+            Atom::Indicator(ref condition) => condition.mentions_where(test),
+            Atom::Sum(ref sum) => {
+                sum.from.mentions_where(test) || sum.to.mentions_where(test)
+                    || sum.body.mentions_where(test)
+            }
+        }
+    }
+
+    fn substitute(&self, f: &mut dyn FnMut(&Atom) -> Option<Expr>) -> Expr {
+        if let Some(expr) = f(self) {
+            return expr;
+        }
+
+        match *self {
+            Atom::Input(_) | Atom::Variable(_) => Expr::atom(self.clone()),
+            Atom::Indicator(ref condition) => Expr::indicator(condition.substitute(f)),
+            Atom::Sum(ref sum) => Expr::sum(
+                sum.variable.clone(),
+                sum.from.substitute(f),
+                sum.to.substitute(f),
+                sum.body.substitute(f),
+            ),
+        }
+    }
+
+    fn evaluate(&self, environment: &mut Environment) -> Option<i64> {
+        match *self {
+            Atom::Input(r) => environment.inputs.get(&r).cloned(),
+            Atom::Variable(ref v) => environment.bindings.get(&v.id).cloned(),
+            Atom::Indicator(ref condition) => condition.holds(environment).map(i64::from),
+            Atom::Sum(ref sum) => {
+                let from = sum.from.evaluate_in(environment)?;
+                let to = sum.to.evaluate_in(environment)?;
+
+                let mut total: i64 = 0;
+                for n in from..=to {
+                    environment.bindings.insert(sum.variable.id, n);
+                    let value = sum.body.evaluate_in(environment);
+                    environment.bindings.remove(&sum.variable.id);
+                    total = total.checked_add(value?)?;
+                }
+
+                Some(total)
+            }
+        }
+    }
+}
+
+impl Condition {
+    // The condition that an expression known to be 0 or 1 is 1.
+    fn from_indicator(expr: Expr) -> Condition {
+        if expr.terms.len() == 1 {
+            if let Some((monomial, &1)) = expr.terms.iter().next() {
+                if let [Atom::Indicator(ref condition)] = monomial[..] {
+                    return (**condition).clone();
+                }
+            }
+        }
+
+        Condition::Zero(expr - Expr::constant(1))
+    }
+
+    fn mentions_where(&self, test: &dyn Fn(usize) -> bool) -> bool {
+        match *self {
+            Condition::Zero(ref expr) => expr.mentions_where(test),
+            Condition::Exists {
+                ref from,
+                ref to,
+                ref condition,
+                ..
+            } => from.mentions_where(test) || to.mentions_where(test)
+                || condition.mentions_where(test),
+        }
+    }
+
+    fn substitute(&self, f: &mut dyn FnMut(&Atom) -> Option<Expr>) -> Condition {
+        match *self {
+            Condition::Zero(ref expr) => Condition::Zero(expr.substitute(f)),
+            Condition::Exists {
+                ref variable,
+                ref from,
+                ref to,
+                ref condition,
+            } => Condition::Exists {
+                variable: variable.clone(),
+                from: from.substitute(f),
+                to: to.substitute(f),
+                condition: Box::new(condition.substitute(f)),
+            },
+        }
+    }
+
+    fn holds(&self, environment: &mut Environment) -> Option<bool> {
+        let (variable, from, to, condition) = match *self {
+            Condition::Zero(ref expr) => return expr.evaluate_in(environment).map(|n| n == 0),
+            Condition::Exists {
+                ref variable,
+                ref from,
+                ref to,
+                ref condition,
+            } => (variable, from, to, condition),
+        };
+
+        let from = from.evaluate_in(environment)?;
+        let to = to.evaluate_in(environment)?;
+        if to < from {
+            return Some(false);
+        }
+
+        // Solve a linear condition rather than trying every value.
+        if let Condition::Zero(ref expr) = **condition {
+            if let Some((alpha, beta)) = expr.split_linear(variable) {
+                let alpha = alpha.evaluate_in(environment)?;
+                let beta = beta.evaluate_in(environment)?;
+                return Some(if beta == 0 {
+                    alpha == 0
+                } else {
+                    alpha % beta == 0 && (from..=to).contains(&(-alpha / beta))
+                });
+            }
+        }
+
+        // Likewise, look for a product in two ranges by factorizing it.
+        if let Condition::Exists {
+            variable: ref inner,
+            from: ref inner_from,
+            to: ref inner_to,
+            condition: ref inner_condition,
+        } = **condition
+        {
+            if let Condition::Zero(ref expr) = **inner_condition {
+                if !inner_from.mentions(variable) && !inner_to.mentions(variable) {
+                    if let Some((c, rest)) = expr.split_product(variable, inner) {
+                        let rest = rest.evaluate_in(environment)?;
+                        let inner_from = inner_from.evaluate_in(environment)?;
+                        let inner_to = inner_to.evaluate_in(environment)?;
+                        if rest % c != 0 {
+                            return Some(false);
+                        }
+
+                        let product = -rest / c;
+                        if product > 0 && from >= 1 && inner_from >= 1 {
+                            return Some(product_in_ranges(
+                                product,
+                                (from, to),
+                                (inner_from, inner_to),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut found = false;
+        for n in from..=to {
+            environment.bindings.insert(variable.id, n);
+            let holds = condition.holds(environment);
+            environment.bindings.remove(&variable.id);
+            if holds? {
+                found = true;
+                break;
+            }
+        }
+
+        Some(found)
+    }
+}
+
+// Whether n == x * y for any x and y in the given inclusive ranges, all of
+// which are positive.
+fn product_in_ranges(n: i64, xs: (i64, i64), ys: (i64, i64)) -> bool {
+    let in_range = |n: i64, range: (i64, i64)| n >= range.0 && n <= range.1;
+
+    let mut k: i64 = 1;
+    while k * k <= n {
+        if n % k == 0 {
+            let other = n / k;
+            if (in_range(k, xs) && in_range(other, ys)) || (in_range(other, xs) && in_range(k, ys))
+            {
+                return true;
+            }
+        }
+
+        k += 1;
+    }
+
+    false
+}
+
+struct Environment<'a> {
+    inputs: &'a BTreeMap<Register, i64>,
+    bindings: HashMap<usize, i64>,
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Expr {
+    pub fn constant(n: i64) -> Expr {
+        let mut expr = Expr::default();
+        expr.add_term(Vec::new(), n);
+        expr
+    }
+
+    pub fn zero() -> Expr {
+        Expr::default()
+    }
+
+    pub fn atom(atom: Atom) -> Expr {
+        let mut expr = Expr::default();
+        expr.add_term(vec![atom], 1);
+        expr
+    }
+
+    pub fn input(register: Register) -> Expr {
+        Expr::atom(Atom::Input(register))
+    }
+
+    pub fn variable(variable: &Variable) -> Expr {
+        Expr::atom(Atom::Variable(variable.clone()))
+    }
+
+    // 1 if the condition holds and 0 if not, simplified where possible.
+    pub fn indicator(condition: Condition) -> Expr {
+        let expr = match condition {
+            Condition::Zero(expr) => expr,
+            Condition::Exists {
+                variable,
+                from,
+                to,
+                condition,
+            } => return Expr::exists(variable, from, to, Expr::indicator(*condition)),
+        };
+
+        if let Some(n) = expr.as_constant() {
+            return Expr::constant(i64::from(n == 0));
+        }
+
+        // An expression of the form c0 + c1 * [A] is zero either never,
+        // always when A holds, or always when it does not.
+        let c0 = expr.terms.get(&Vec::new()).cloned().unwrap_or(0);
+        let rest: Vec<(&Vec<Atom>, &i64)> = expr.terms.iter().filter(|t| !t.0.is_empty()).collect();
+        if let [(monomial, &c1)] = rest[..] {
+            if monomial.iter().all(Atom::is_indicator) {
+                let a = Expr::from_term(monomial.clone(), 1);
+                return if c0 == 0 {
+                    Expr::constant(1) - a
+                } else if c0 + c1 == 0 {
+                    a
+                } else {
+                    Expr::zero()
+                };
+            }
+        }
+
+        // Scale the expression so that equivalent conditions look the same.
+        let divisor = expr.terms.values().fold(0, |d, &c| gcd(d, c));
+        let sign = match expr.terms.iter().rev().find(|t| !t.0.is_empty()) {
+            Some((_, &c)) if c < 0 => -1,
+            _ => 1,
+        };
+        let mut normalized = Expr::default();
+        for (monomial, &c) in &expr.terms {
+            normalized.add_term(monomial.clone(), sign * c / divisor);
+        }
+
+        Expr::atom(Atom::Indicator(Box::new(Condition::Zero(normalized))))
+    }
+
+    // 1 if `body`, which must be 0 or 1, is 1 for any value of `variable` in
+    // the inclusive range.
+    pub fn exists(variable: Variable, from: Expr, to: Expr, body: Expr) -> Expr {
+        if let (Some(from), Some(to)) = (from.as_constant(), to.as_constant()) {
+            if to < from {
+                return Expr::zero();
+            }
+        }
+
+        if from == to {
+            return body.replace(&variable, &from);
+        }
+
+        if body.as_constant() == Some(0) {
+            return Expr::zero();
+        }
+
+        Expr::atom(Atom::Indicator(Box::new(Condition::Exists {
+            variable,
+            from,
+            to,
+            condition: Box::new(Condition::from_indicator(body)),
+        })))
+    }
+
+    // The sum of `body` for every value of `variable` in the inclusive range.
+    pub fn sum(variable: Variable, from: Expr, to: Expr, body: Expr) -> Expr {
+        if let (Some(from), Some(to)) = (from.as_constant(), to.as_constant()) {
+            if to < from {
+                return Expr::zero();
+            }
+        }
+
+        if from == to {
+            return body.replace(&variable, &from);
+        }
+
+        // Terms that do not depend on the variable are just multiplied by the
+        // number of values it takes.
+        let count = to.clone() - from.clone() + Expr::constant(1);
+        let mut result = Expr::zero();
+        let mut rest = Expr::zero();
+        for (monomial, &c) in &body.terms {
+            let term = Expr::from_term(monomial.clone(), c);
+            if term.mentions(&variable) {
+                rest = rest + term;
+            } else {
+                result = result + count.clone() * term;
+            }
+        }
+
+        if !rest.is_zero() {
+            result = result + Expr::atom(Atom::Sum(Box::new(Sum {
+                variable,
+                from,
+                to,
+                body: rest,
+            })));
+        }
+
+        result
+    }
+
+    fn from_term(monomial: Vec<Atom>, c: i64) -> Expr {
+        let mut expr = Expr::default();
+        expr.add_term(monomial, c);
+        expr
+    }
+
+    fn add_term(&mut self, monomial: Vec<Atom>, c: i64) {
+        let coefficient = {
+            let entry = self.terms.entry(monomial.clone()).or_insert(0);
+            *entry = entry.wrapping_add(c);
+            *entry
+        };
+
+        if coefficient == 0 {
+            self.terms.remove(&monomial);
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.len() {
+            0 => Some(0),
+            1 => self.terms.get(&Vec::new()).cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn scale(&self, n: i64) -> Expr {
+        let mut expr = Expr::default();
+        for (monomial, &c) in &self.terms {
+            expr.add_term(monomial.clone(), c.wrapping_mul(n));
+        }
+        expr
+    }
+
+    fn mentions_where(&self, test: &dyn Fn(usize) -> bool) -> bool {
+        self.terms
+            .keys()
+            .any(|monomial| monomial.iter().any(|atom| atom.mentions_where(test)))
+    }
+
+    // Whether the variable appears anywhere in the expression.
+    pub fn mentions(&self, variable: &Variable) -> bool {
+        self.mentions_where(&|id| id == variable.id)
+    }
+
+    pub fn mentions_any(&self, ids: &BTreeSet<usize>) -> bool {
+        self.mentions_where(&|id| ids.contains(&id))
+    }
+
+    // Rebuilds the expression with every atom for which `f` returns a value
+    // replaced by that value.
+    pub fn substitute(&self, f: &mut dyn FnMut(&Atom) -> Option<Expr>) -> Expr {
+        let mut result = Expr::zero();
+        for (monomial, &c) in &self.terms {
+            let mut product = Expr::constant(c);
+            for atom in monomial {
+                product = product * atom.substitute(f);
+            }
+            result = result + product;
+        }
+
+        result
+    }
+
+    pub fn replace(&self, variable: &Variable, value: &Expr) -> Expr {
+        self.substitute(&mut |atom| match *atom {
+            Atom::Variable(ref v) if v == variable => Some(value.clone()),
+            _ => None,
+        })
+    }
+
+    // Writes the expression as alpha + beta * variable, where neither alpha
+    // nor beta mentions the variable.
+    pub fn split_linear(&self, variable: &Variable) -> Option<(Expr, Expr)> {
+        let mut alpha = Expr::zero();
+        let mut beta = Expr::zero();
+
+        for (monomial, &c) in &self.terms {
+            let is_variable = |atom: &Atom| *atom == Atom::Variable(variable.clone());
+            let (matching, others): (Vec<&Atom>, Vec<&Atom>) =
+                monomial.iter().partition(|atom| is_variable(atom));
+            if others.iter().any(|atom| atom.mentions_where(&|id| id == variable.id)) {
+                return None;
+            }
+
+            let term = Expr::from_term(others.into_iter().cloned().collect(), c);
+            match matching.len() {
+                0 => alpha = alpha + term,
+                1 => beta = beta + term,
+                _ => return None,
+            }
+        }
+
+        Some((alpha, beta))
+    }
+
+    // Divides every term by `factor`, which must be a single monomial with a
+    // coefficient of 1 that divides each of them.
+    pub fn factor_out(&self, factor: &Expr) -> Option<Expr> {
+        let divisor = match factor.terms.iter().next() {
+            Some((monomial, &1)) if factor.terms.len() == 1 => monomial,
+            _ => return None,
+        };
+
+        let mut quotient = Expr::zero();
+        for (monomial, &c) in &self.terms {
+            let mut rest = monomial.clone();
+            for atom in divisor {
+                let position = rest.iter().position(|a| a == atom)?;
+                rest.remove(position);
+            }
+            quotient.add_term(rest, c);
+        }
+
+        Some(quotient)
+    }
+
+    // Writes the expression as c * x * y + rest, where c is a nonzero
+    // constant and rest mentions neither variable.
+    fn split_product(&self, x: &Variable, y: &Variable) -> Option<(i64, Expr)> {
+        let mut product = vec![Atom::Variable(x.clone()), Atom::Variable(y.clone())];
+        product.sort();
+
+        let c = *self.terms.get(&product)?;
+        let mut rest = self.clone();
+        rest.terms.remove(&product);
+        if rest.mentions(x) || rest.mentions(y) {
+            return None;
+        }
+
+        Some((c, rest))
+    }
+
+    // The value of the expression given the value of every input it uses.
+    pub fn evaluate(&self, inputs: &BTreeMap<Register, i64>) -> Option<i64> {
+        self.evaluate_in(&mut Environment {
+            inputs,
+            bindings: HashMap::new(),
+        })
+    }
+
+    fn evaluate_in(&self, environment: &mut Environment) -> Option<i64> {
+        let mut total: i64 = 0;
+        for (monomial, &c) in &self.terms {
+            let mut product = c;
+            for atom in monomial {
+                if product == 0 {
+                    break;
+                }
+                product = product.checked_mul(atom.evaluate(environment)?)?;
+            }
+            total = total.checked_add(product)?;
+        }
+
+        Some(total)
+    }
+
+    // Splits the terms into those with positive and negative coefficients,
+    // negating the latter.
+    fn split_signs(&self) -> (Expr, Expr) {
+        let mut positive = Expr::zero();
+        let mut negative = Expr::zero();
+        for (monomial, &c) in &self.terms {
+            if c > 0 {
+                positive.add_term(monomial.clone(), c);
+            } else {
+                negative.add_term(monomial.clone(), c.wrapping_neg());
+            }
+        }
+
+        (positive, negative)
+    }
+}
+
+impl Add for Expr {
+    type Output = Expr;
+
+    fn add(mut self, other: Expr) -> Expr {
+        for (monomial, c) in other.terms {
+            self.add_term(monomial, c);
+        }
+        self
+    }
+}
+
+impl Sub for Expr {
+    type Output = Expr;
+
+    fn sub(self, other: Expr) -> Expr {
+        self + -other
+    }
+}
+
+impl Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        self.scale(-1)
+    }
+}
+
+impl Mul for Expr {
+    type Output = Expr;
+
+    fn mul(self, other: Expr) -> Expr {
+        let mut product = Expr::zero();
+        for (left, &a) in &self.terms {
+            for (right, &b) in &other.terms {
+                let mut monomial: Vec<Atom> = left.iter().chain(right.iter()).cloned().collect();
+                monomial.sort();
+                // Conditions are 0 or 1, so multiplying by one twice is the
+                // same as multiplying by it once.
+                monomial.dedup_by(|x, y| x == y && x.is_indicator());
+                product.add_term(monomial, a.wrapping_mul(b));
+            }
+        }
+
+        product
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Atom::Input(r) => write!(f, "{}", r),
+            Atom::Variable(ref v) => write!(f, "{}", v.name),
+            Atom::Indicator(ref condition) => write!(f, "[{}]", condition),
+            Atom::Sum(ref sum) => write!(
+                f,
+                "sum({} in [{}, {}]: {})",
+                sum.variable.name, sum.from, sum.to, sum.body
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Condition::Zero(ref expr) => {
+                // Keep any constant on the right, as in "d*e == 17*k + 100".
+                let (positive, negative) = expr.split_signs();
+                if positive.terms.contains_key(&Vec::new()) {
+                    write!(f, "{} == {}", negative, positive)
+                } else {
+                    write!(f, "{} == {}", positive, negative)
+                }
+            }
+            Condition::Exists {
+                ref variable,
+                ref from,
+                ref to,
+                ref condition,
+            } => write!(
+                f,
+                "exists {} in [{}, {}]: {}",
+                variable.name, from, to, condition
+            ),
+        }
+    }
+}
+
+// Writes terms with variables before constants, and positive terms before
+// negative ones, so that "b - 17" reads naturally.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+
+        let mut terms: Vec<(&Vec<Atom>, &i64)> = self.terms.iter().collect();
+        terms.sort_by_key(|&(monomial, &c)| (c < 0, monomial.is_empty()));
+
+        for (n, &(monomial, &c)) in terms.iter().enumerate() {
+            match (n, c < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            let magnitude = c.wrapping_abs();
+            if monomial.is_empty() {
+                write!(f, "{}", magnitude)?;
+                continue;
+            }
+
+            if magnitude != 1 {
+                write!(f, "{}*", magnitude)?;
+            }
+            let atoms: Vec<String> = monomial.iter().map(|atom| atom.to_string()).collect();
+            write!(f, "{}", atoms.join("*"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(id: usize, name: &str) -> Variable {
+        Variable {
+            id,
+            name: name.to_string(),
+        }
+    }
+
+    fn inputs(values: &[(Register, i64)]) -> BTreeMap<Register, i64> {
+        values.iter().cloned().collect()
+    }
+
+    #[test]
+    fn normal_form() {
+        let a = Expr::input('a');
+        let b = Expr::input('b');
+        let left = (a.clone() + b.clone()) * (a.clone() - b.clone());
+        let right = a.clone() * a.clone() - b.clone() * b.clone();
+        assert_eq!(left, right);
+        assert_eq!((a.clone() - a.clone()).as_constant(), Some(0));
+        assert_eq!(
+            (Expr::constant(3) - b.scale(2) + a.clone() * b).to_string(),
+            "a*b + 3 - 2*b"
+        );
+        assert_eq!((Expr::constant(-5) - a).to_string(), "-a - 5");
+    }
+
+    #[test]
+    fn simplify_indicators() {
+        let a = Expr::input('a');
+        let zero = |expr: Expr| Expr::indicator(Condition::Zero(expr));
+
+        assert_eq!(zero(Expr::zero()), Expr::constant(1));
+        assert_eq!(zero(Expr::constant(4)), Expr::zero());
+        assert_eq!(zero(a.scale(-2) + Expr::constant(6)), zero(a.clone() - Expr::constant(3)));
+        assert_eq!(zero(a.clone()).to_string(), "[a == 0]");
+
+        let flag = Expr::constant(1) - zero(a.clone());
+        assert_eq!(zero(flag.clone()), zero(a.clone()));
+        assert_eq!(zero(flag.clone() - Expr::constant(1)), Expr::constant(1) - zero(a.clone()));
+        assert_eq!(zero(a.clone()) * zero(a.clone()), zero(a.clone()));
+        assert_eq!(zero(flag + Expr::constant(1)), Expr::zero());
+    }
+
+    #[test]
+    fn bound_variables() {
+        let k = variable(0, "k");
+        let n = Expr::input('n');
+
+        let sum = Expr::sum(
+            k.clone(),
+            Expr::constant(1),
+            n.clone(),
+            Expr::variable(&k) + Expr::constant(2),
+        );
+        assert_eq!(sum.to_string(), "2*n + sum(k in [1, n]: k)");
+        assert_eq!(sum.evaluate(&inputs(&[('n', 4)])), Some(18));
+        assert_eq!(
+            Expr::sum(k.clone(), n.clone(), n.clone(), Expr::variable(&k)),
+            n.clone()
+        );
+
+        let exists = Expr::exists(
+            k.clone(),
+            Expr::constant(0),
+            Expr::constant(10),
+            Expr::indicator(Condition::Zero(Expr::variable(&k).scale(3) - n.clone())),
+        );
+        assert_eq!(exists.to_string(), "[exists k in [0, 10]: 3*k == n]");
+        assert_eq!(exists.evaluate(&inputs(&[('n', 27)])), Some(1));
+        assert_eq!(exists.evaluate(&inputs(&[('n', 28)])), Some(0));
+        assert_eq!(exists.evaluate(&inputs(&[('n', 33)])), Some(0));
+        assert_eq!(exists.evaluate(&inputs(&[])), None);
+
+        let empty = Expr::exists(k, Expr::constant(1), Expr::constant(0), Expr::constant(1));
+        assert_eq!(empty, Expr::zero());
+    }
+
+    #[test]
+    fn find_factors() {
+        let (d, e) = (variable(0, "d"), variable(1, "e"));
+        let n = Expr::input('n');
+        let upper = n.clone() - Expr::constant(1);
+        let product = Expr::variable(&d) * Expr::variable(&e) - n;
+
+        let composite = Expr::exists(
+            d,
+            Expr::constant(2),
+            upper.clone(),
+            Expr::exists(e, Expr::constant(2), upper, Expr::indicator(Condition::Zero(product))),
+        );
+        assert_eq!(
+            composite.to_string(),
+            "[exists d in [2, n - 1]: exists e in [2, n - 1]: d*e == n]"
+        );
+
+        let composites: Vec<i64> = (2..30)
+            .filter(|&n| composite.evaluate(&inputs(&[('n', n)])) == Some(1))
+            .collect();
+        assert_eq!(
+            composites,
+            vec![4, 6, 8, 9, 10, 12, 14, 15, 16, 18, 20, 21, 22, 24, 25, 26, 27, 28]
+        );
+        assert_eq!(composite.evaluate(&inputs(&[('n', 1_000_003)])), Some(0));
+    }
+}
@@ -1,24 +1,26 @@
-use super::Register;
 use super::errors::Error;
+use register_vm::{self, InstructionSet, Opcode, Operand, Register, Value};
+use std::fmt;
 use std::str::FromStr;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub enum Value {
-    FromRegister(Register),
-    Literal(i64),
+// The registers used by the compositeness test loops in the puzzle input,
+// which look for a divisor and cofactor whose product is the target.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct DivisorSearch {
+    pub flag: Register,
+    pub divisor: Register,
+    pub cofactor: Register,
+    pub target: Register,
+    pub scratch: Register,
 }
 
-impl FromStr for Value {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(n) = s.parse() {
-            Ok(Value::Literal(n))
-        } else if let Some(c) = s.chars().nth(0) {
-            Ok(Value::FromRegister(c))
-        } else {
-            Err(Error::missing_argument())
-        }
+impl fmt::Display for DivisorSearch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.flag, self.divisor, self.cofactor, self.target, self.scratch
+        )
     }
 }
 
@@ -27,55 +29,164 @@ pub enum Instruction {
     Set(Register, Value),
     Subtract(Register, Value),
     Multiply(Register, Value),
-    JumpIfNotZero(Value, Value)
+    JumpIfNotZero(Value, Value),
+    // Macro-instructions produced by the optimizer. Each stands in for a
+    // whole loop that starts at its own position in the program, and skips
+    // the rest of it, so they print but do not parse: written by hand, they
+    // would skip over whatever code happened to follow.
+
+    // x -= v * c; c = 0
+    SubtractProduct(Register, Value, Register),
+    // Clears the flag if divisor * cofactor == target for any cofactor
+    // between its current value and the target.
+    TestDivisor(DivisorSearch),
+    // As TestDivisor, but for every divisor up to the target, with the
+    // cofactor starting from the given value each time.
+    TestComposite(DivisorSearch, i64),
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> &'static str {
+        match *self {
+            Instruction::Set(_, _) => "set",
+            Instruction::Subtract(_, _) => "sub",
+            Instruction::Multiply(_, _) => "mul",
+            Instruction::JumpIfNotZero(_, _) => "jnz",
+            Instruction::SubtractProduct(_, _, _) => "submul",
+            Instruction::TestDivisor(_) => "tdiv",
+            Instruction::TestComposite(_, _) => "tcomp",
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Set(r, ref v) => write!(f, "set {} {}", r, v),
+            Instruction::Subtract(r, ref v) => write!(f, "sub {} {}", r, v),
+            Instruction::Multiply(r, ref v) => write!(f, "mul {} {}", r, v),
+            Instruction::JumpIfNotZero(ref x, ref y) => write!(f, "jnz {} {}", x, y),
+            Instruction::SubtractProduct(x, ref v, c) => write!(f, "submul {} {} {}", x, v, c),
+            Instruction::TestDivisor(ref search) => write!(f, "tdiv {}", search),
+            Instruction::TestComposite(ref search, start) => {
+                write!(
+                    f,
+                    "tcomp {} {} {} {} {} {}",
+                    search.flag,
+                    search.divisor,
+                    search.cofactor,
+                    start,
+                    search.target,
+                    search.scratch
+                )
+            }
+        }
+    }
+}
+
+static OPCODES: &[Opcode<Instruction>] = &[
+    Opcode {
+        mnemonic: "set",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Set(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "sub",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Subtract(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "mul",
+        operands: &[Operand::Register, Operand::Value],
+        build: |a| Instruction::Multiply(a.register(0), a.value(1)),
+    },
+    Opcode {
+        mnemonic: "jnz",
+        operands: &[Operand::Value, Operand::Value],
+        build: |a| Instruction::JumpIfNotZero(a.value(0), a.value(1)),
+    },
+];
+
+impl InstructionSet for Instruction {
+    fn opcodes() -> &'static [Opcode<Instruction>] {
+        OPCODES
+    }
 }
 
 impl FromStr for Instruction {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split_whitespace().collect();
-        let length = parts.len();
-        if length < 2 {
-            return Err(Error::missing_argument());
-        }
+        register_vm::parse(s)
+    }
+}
 
-        let instruction = parts[0];
-        let arg1: Value = parts[1].parse()?;
-
-        let arg2: Option<Value> = if length > 2 {
-            Some(parts[2].parse()?)
-        } else {
-            None
-        };
-
-        match instruction {
-            "set" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Set(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "sub" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Subtract(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "mul" => match arg1 {
-                Value::FromRegister(r) => match arg2 {
-                    Some(a) => Ok(Instruction::Multiply(r, a)),
-                    None => Err(Error::missing_argument()),
-                },
-                Value::Literal(_) => Err(Error::wrong_type()),
-            },
-            "jnz" => match arg2 {
-                Some(v) => Ok(Instruction::JumpIfNotZero(arg1, v)),
-                None => Err(Error::missing_argument()),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ErrorKind;
+    use proptest::prelude::*;
+
+    fn register() -> impl Strategy<Value = Register> {
+        (b'a'..=b'z').prop_map(char::from)
+    }
+
+    fn value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            register().prop_map(Value::FromRegister),
+            any::<i64>().prop_map(Value::Literal),
+        ]
+    }
+
+    fn instruction() -> impl Strategy<Value = Instruction> {
+        prop_oneof![
+            (register(), value()).prop_map(|(r, v)| Instruction::Set(r, v)),
+            (register(), value()).prop_map(|(r, v)| Instruction::Subtract(r, v)),
+            (register(), value()).prop_map(|(r, v)| Instruction::Multiply(r, v)),
+            (value(), value()).prop_map(|(x, y)| Instruction::JumpIfNotZero(x, y)),
+        ]
+    }
+
+    fn macro_instruction() -> impl Strategy<Value = Instruction> {
+        prop_oneof![
+            (register(), value(), register())
+                .prop_map(|(x, v, c)| Instruction::SubtractProduct(x, v, c)),
+            search().prop_map(Instruction::TestDivisor),
+            (search(), any::<i64>()).prop_map(|(s, n)| Instruction::TestComposite(s, n)),
+        ]
+    }
+
+    fn search() -> impl Strategy<Value = DivisorSearch> {
+        (register(), register(), register(), register(), register()).prop_map(
+            |(flag, divisor, cofactor, target, scratch)| DivisorSearch {
+                flag,
+                divisor,
+                cofactor,
+                target,
+                scratch,
             },
-            _ => Err(Error::unrecognized_instruction()),
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn print_then_parse(instruction in instruction()) {
+            let printed = instruction.to_string();
+            prop_assert_eq!(printed.parse::<Instruction>().unwrap(), instruction);
+        }
+
+        #[test]
+        fn macro_instructions_do_not_parse(instruction in macro_instruction()) {
+            let error = instruction.to_string().parse::<Instruction>().unwrap_err();
+            prop_assert_eq!(error.kind, ErrorKind::UnrecognizedInstruction);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn puzzle_input_round_trips() {
+        for line in include_str!("puzzle_input.txt").lines() {
+            let instruction: Instruction = line.parse().unwrap();
+            assert_eq!(instruction.to_string(), line.trim());
+        }
+    }
+}
@@ -1,85 +1,312 @@
+#[cfg(test)]
+extern crate proptest;
+extern crate register_vm;
+
+pub mod bytecode;
+pub mod control_flow;
+pub mod debugger;
+pub mod decompiler;
 pub mod errors;
+pub mod expression;
 pub mod instructions;
-pub use instructions::{Instruction, Value};
-use std::collections::{HashMap};
+pub mod optimizer;
+pub mod profiler;
+pub mod symbolic;
+pub use bytecode::Op;
+pub use control_flow::ControlFlowGraph;
+pub use debugger::{Debugger, Stop};
+pub use decompiler::decompile;
+pub use expression::{Condition, Expr};
+pub use instructions::{DivisorSearch, Instruction};
+pub use optimizer::optimize;
+pub use profiler::{HotLoop, Profile};
+pub use symbolic::{execute_symbolically, Path, SymbolicError};
+pub use register_vm::{Register, Trace, Value};
+use errors::{RuntimeError, RuntimeErrorKind};
+use register_vm::bytecode::Slot;
+use register_vm::{Budget, Control, Execute, Interpreter, Outcome, Registers};
+use std::convert::TryFrom;
 
-pub type Register = char;
+// The largest register value the macro-instructions will shortcut, chosen so
+// that multiplying two such values cannot overflow.
+const MACRO_LIMIT: i64 = 1 << 31;
 
-pub struct Processor {
-    registers: HashMap<Register, i64>,
-    multiplications: usize,
-}
+impl Execute for Instruction {
+    // The number of multiplications performed so far.
+    type State = usize;
+    // A jump out of the program, in either direction, halts it rather than
+    // failing.
+    type Error = RuntimeErrorKind;
+    type Op = Op;
 
-impl Processor {
-    pub fn debug() -> Processor {
-        Processor {
-            registers: HashMap::new(),
-            multiplications: 0,
-        }
+    fn compile(&self) -> Op {
+        bytecode::compile(self)
     }
 
-    pub fn release() -> Processor {
-        let mut registers = HashMap::new();
-        registers.insert('a', 1);
-        Processor {
-            registers: registers,
-            multiplications: 0,
-        }
-    }
+    fn execute(op: &Op, registers: &mut Registers, multiplications: &mut usize) -> Result<Control, RuntimeErrorKind> {
+        match *op {
+            Op::SetRegister(x, y) => {
+                let value = registers.load(y);
+                registers.store(x, value);
+            }
+            Op::SetLiteral(x, n) => registers.store(x, n),
+            Op::SubtractRegister(x, y) => {
+                let value = registers.load(y);
+                subtract(registers, x, value)?;
+            }
+            Op::SubtractLiteral(x, n) => subtract(registers, x, n)?,
+            Op::MultiplyRegister(x, y) => {
+                *multiplications += 1;
+                let value = registers.load(y);
+                multiply(registers, x, value)?;
+            }
+            Op::MultiplyLiteral(x, n) => {
+                *multiplications += 1;
+                multiply(registers, x, n)?;
+            }
+            Op::Jump(offset) => return Ok(Control::Jump(offset)),
+            Op::JumpIfNotZero(x, offset) => {
+                if registers.load(x) != 0 {
+                    return Ok(Control::Jump(offset));
+                }
+            }
+            Op::JumpBy(condition, offset) => {
+                if registers.source(condition) != 0 {
+                    return Ok(Control::Jump(registers.load(offset)));
+                }
+            }
+            Op::Nop => {}
+            Op::SubtractProduct(x, value, counter) => {
+                let value = registers.source(value);
+                let count = registers.load(counter);
+                let result = i128::from(registers.load(x)) - i128::from(value) * i128::from(count);
 
-    pub fn multiplication_count(&self) -> usize {
-        self.multiplications
-    }
+                // The loop only counts down to zero from above, and each step
+                // moves x towards the result, so if that fits then so does
+                // everything in between.
+                if let (true, Ok(result)) = (count > 0, i64::try_from(result)) {
+                    registers.store(x, result);
+                    registers.store(counter, 0);
+                    return Ok(Control::Jump(3));
+                }
 
-    pub fn execute(&mut self, instructions: &[Instruction]) {
-        let mut index: usize = 0;
-        let instruction_count = instructions.len();
+                // Otherwise run the loop as written, so that it overflows
+                // where the original would.
+                subtract(registers, x, value)?;
+            }
+            Op::TestDivisor(search) => {
+                let divisor = registers.load(search.divisor);
+                let cofactor = registers.load(search.cofactor);
+                let target = registers.load(search.target);
 
-        while index < instruction_count {
-            match instructions[index] {
-                Instruction::Set(ref register, ref value) => {
-                    let x = self.get_value(value);
-                    *self.get_mut(register) = x;
-                }
-                Instruction::Subtract(ref register, ref value) => {
-                    let x = self.get_value(value);
-                    *self.get_mut(register) -= x;
-                }
-                Instruction::Multiply(ref register, ref value) => {
-                    self.multiplications += 1;
-                    let x = self.get_value(value);
-                    *self.get_mut(register) *= x;
+                if let Some((found, count)) = divisor_test(divisor, cofactor, target) {
+                    *multiplications += count;
+                    if found {
+                        registers.store(search.flag, 0);
+                    }
+                    registers.store(search.cofactor, target);
+                    registers.store(search.scratch, 0);
+                    return Ok(Control::Jump(9));
                 }
-                Instruction::JumpIfNotZero(_, _) => {}
+
+                // Outside the range the shortcut handles, run the loop as written.
+                registers.store(search.scratch, divisor);
             }
+            Op::TestComposite(search, start) => {
+                let target = registers.load(search.target);
+                let divisor = registers.load(search.divisor);
 
-            index = match instructions[index] {
-                Instruction::JumpIfNotZero(ref condition, ref value) => {
-                    if self.get_value(condition) == 0 {
-                        index + 1
-                    } else {
-                        ((index as i64) + self.get_value(value)) as usize
+                if let Some((found, count)) = composite_test(divisor, target, start) {
+                    *multiplications += count;
+                    if found {
+                        registers.store(search.flag, 0);
                     }
+                    registers.store(search.divisor, target);
+                    registers.store(search.cofactor, target);
+                    registers.store(search.scratch, 0);
+                    return Ok(Control::Jump(14));
                 }
-                _ => index + 1,
+
+                registers.store(search.cofactor, start);
             }
         }
+
+        Ok(Control::Next)
     }
 
-    fn get_value(&self, value: &Value) -> i64 {
-        match *value {
-            Value::FromRegister(r) => self.get_register(r),
-            Value::Literal(v) => v,
+    fn jump_before_start() -> Result<(), RuntimeErrorKind> {
+        Ok(())
+    }
+}
+
+pub struct Processor<'a> {
+    interpreter: Interpreter<'a, Instruction>,
+}
+
+impl<'a> Processor<'a> {
+    pub fn debug(instructions: &'a [Instruction]) -> Processor<'a> {
+        Processor {
+            interpreter: Interpreter::new(instructions, 0),
+        }
+    }
+
+    pub fn release(instructions: &'a [Instruction]) -> Processor<'a> {
+        let mut processor = Processor::debug(instructions);
+        processor.set_register('a', 1);
+        processor
+    }
+
+    pub fn instructions(&self) -> &'a [Instruction] {
+        self.interpreter.instructions()
+    }
+
+    pub fn multiplication_count(&self) -> usize {
+        *self.interpreter.state()
+    }
+
+    // The index of the next instruction to be executed.
+    pub fn program_counter(&self) -> usize {
+        self.interpreter.program_counter()
+    }
+
+    pub fn current_instruction(&self) -> Option<&'a Instruction> {
+        self.interpreter.current_instruction()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.interpreter.is_halted()
+    }
+
+    // The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.interpreter.steps()
+    }
+
+    // Runs the program to completion, or until an instruction overflows.
+    // The registers and multiplication count are left as they were when it
+    // stopped.
+    pub fn execute(&mut self) -> Result<(), RuntimeError> {
+        let result = self.interpreter.run();
+        self.check(result).map(|_| ())
+    }
+
+    // Like `execute`, but also gives up if the program exceeds the budget.
+    pub fn execute_with_budget(&mut self, budget: &Budget) -> Result<(), RuntimeError> {
+        let result = self.interpreter.run_with_budget(budget);
+        self.check(result).map(|_| ())
+    }
+
+    // Like `execute`, but records where the program spends its time.
+    pub fn profile(&mut self) -> Result<Profile, RuntimeError> {
+        let instructions = self.instructions();
+        let mut profile = Profile::new(instructions.len());
+
+        while !self.is_halted() {
+            let index = self.program_counter();
+            self.step()?;
+            profile.record(instructions, index, self.program_counter());
         }
+
+        Ok(profile)
     }
 
-    fn get_mut(&mut self, register: &Register) -> &mut i64 {
-        self.registers.entry(register.clone()).or_insert(0)
+    // Like `execute`, but records every instruction executed and the
+    // registers it changed.
+    pub fn trace(&mut self) -> Result<Trace, RuntimeError> {
+        self.interpreter.start_trace();
+        let result = self.execute();
+        let trace = self.interpreter.take_trace().expect("the trace was started");
+        result.map(|_| trace)
+    }
+
+    // Executes the next instruction, if the program has not finished.
+    pub fn step(&mut self) -> Result<Outcome, RuntimeError> {
+        let result = self.interpreter.step();
+        self.check(result)
+    }
+
+    // An error from the interpreter leaves it on the failing instruction.
+    fn check(&self, result: Result<Outcome, RuntimeErrorKind>) -> Result<Outcome, RuntimeError> {
+        result.map_err(|kind| RuntimeError {
+            kind,
+            index: self.program_counter(),
+        })
     }
 
     pub fn get_register(&self, register: Register) -> i64 {
-        *self.registers.get(&register).unwrap_or(&0)
+        self.interpreter.registers().get(register)
+    }
+
+    pub fn set_register(&mut self, register: Register, value: i64) {
+        self.interpreter.registers_mut().set(register, value);
+    }
+
+    // Every register that has been written to, in alphabetical order.
+    pub fn registers(&self) -> Vec<(Register, i64)> {
+        let mut registers: Vec<(Register, i64)> =
+            self.interpreter.registers().to_map().into_iter().collect();
+        registers.sort();
+        registers
+    }
+}
+
+fn subtract(registers: &mut Registers, x: Slot, value: i64) -> Result<(), RuntimeErrorKind> {
+    let difference = registers.load(x).checked_sub(value).ok_or(RuntimeErrorKind::Overflow)?;
+    registers.store(x, difference);
+    Ok(())
+}
+
+fn multiply(registers: &mut Registers, x: Slot, value: i64) -> Result<(), RuntimeErrorKind> {
+    let product = registers.load(x).checked_mul(value).ok_or(RuntimeErrorKind::Overflow)?;
+    registers.store(x, product);
+    Ok(())
+}
+
+// The effect of the loop that TestDivisor replaces, if it is run with
+// values small enough that none of its multiplications overflow: whether it
+// clears the flag, and how many multiplications it performs.
+fn divisor_test(divisor: i64, cofactor: i64, target: i64) -> Option<(bool, usize)> {
+    if !(0..=MACRO_LIMIT).contains(&divisor) || cofactor < 0 || cofactor >= target
+        || target > MACRO_LIMIT
+    {
+        return None;
     }
+
+    let found = divisor > 0 && target % divisor == 0 && {
+        let quotient = target / divisor;
+        quotient >= cofactor && quotient < target
+    };
+
+    Some((found, (target - cofactor) as usize))
+}
+
+fn composite_test(divisor: i64, target: i64, start: i64) -> Option<(bool, usize)> {
+    if divisor < 0 || divisor >= target || start < 0 || start >= target || target > MACRO_LIMIT {
+        return None;
+    }
+
+    let count = ((target - divisor) * (target - start)) as usize;
+    Some((has_factors(target, divisor, start), count))
+}
+
+// Whether target == p * q for some p and q that are both less than the
+// target and no smaller than the given minimums.
+fn has_factors(target: i64, min_p: i64, min_q: i64) -> bool {
+    let in_range = |p: i64, q: i64| p >= min_p && q >= min_q && p < target && q < target;
+
+    let mut k = 1;
+    while k * k <= target {
+        if target % k == 0 {
+            let other = target / k;
+            if in_range(k, other) || in_range(other, k) {
+                return true;
+            }
+        }
+
+        k += 1;
+    }
+
+    false
 }
 
 pub fn primes_up_to(n: usize) -> Vec<usize> {
@@ -101,11 +328,115 @@ pub fn primes_up_to(n: usize) -> Vec<usize> {
 
 #[cfg(test)]
 mod tests {
-    use super::primes_up_to;
+    use super::*;
+    use register_vm::{Divergence, Halted};
+
+    fn parse(program: &str) -> Vec<Instruction> {
+        program.lines().map(|line| line.parse().unwrap()).collect()
+    }
 
     #[test]
     fn find_primes_up_to_20() {
         let primes = primes_up_to(20);
         assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn trace_matches_execute() {
+        let program = parse("set b 3\nsub c -2\nsub b 1\njnz b -2");
+        let trace = Processor::debug(&program).trace().unwrap();
+        let mut executed = Processor::debug(&program);
+        executed.execute().unwrap();
+
+        assert_eq!(trace.len(), 10);
+        assert_eq!(trace.events()[9].instruction, "jnz b -2");
+        assert_eq!(
+            trace.registers_at(trace.len()).into_iter().collect::<Vec<_>>(),
+            executed.registers()
+        );
+    }
+
+    #[test]
+    fn optimized_trace_diverges_at_first_macro() {
+        let program = parse("set a 3\nsub b -5\nsub c 1\njnz c -2\nsub a 1");
+        let optimized_program = optimize(&program);
+        let mut original = Processor::debug(&program);
+        original.set_register('c', 2);
+        let mut optimized = Processor::debug(&optimized_program);
+        optimized.set_register('c', 2);
+
+        let original = original.trace().unwrap();
+        let optimized = optimized.trace().unwrap();
+
+        match original.first_divergence(&optimized) {
+            Some(Divergence::Step { step, right, .. }) => {
+                assert_eq!(step, 1);
+                assert_eq!(right.unwrap().instruction, "submul b -5 c");
+            }
+            other => panic!("unexpected divergence {:?}", other),
+        }
+    }
+
+    #[test]
+    fn budget_stops_endless_programs() {
+        let program = parse("set a 1\nsub b 1\njnz a -1");
+        let budget = Budget {
+            max_steps: Some(1000),
+            ..Budget::default()
+        };
+        let mut processor = Processor::debug(&program);
+        assert_eq!(
+            processor.execute_with_budget(&budget).map_err(|e| e.kind),
+            Err(RuntimeErrorKind::Halted(Halted::StepLimit))
+        );
+        assert_eq!(processor.get_register('b'), -500);
+
+        let program = parse("set a 1\nset b 2\nmul b 1\njnz a -1");
+        let budget = Budget {
+            detect_loops: true,
+            ..Budget::default()
+        };
+        assert_eq!(
+            Processor::debug(&program).execute_with_budget(&budget).map_err(|e| e.kind),
+            Err(RuntimeErrorKind::Halted(Halted::LoopDetected {
+                first_seen: 2,
+                period: 2,
+            }))
+        );
+
+        let program = parse("set a 3\nsub a 1\njnz a -1");
+        assert_eq!(Processor::debug(&program).execute_with_budget(&budget), Ok(()));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let program = parse("set a 9223372036854775807\nmul a 2");
+        let mut processor = Processor::debug(&program);
+        let error = processor.execute().unwrap_err();
+
+        assert_eq!(
+            error,
+            RuntimeError {
+                kind: RuntimeErrorKind::Overflow,
+                index: 1,
+            }
+        );
+        assert_eq!(error.to_string(), "Arithmetic overflow at instruction 1");
+        assert_eq!(processor.program_counter(), 1);
+        assert_eq!(processor.get_register('a'), i64::MAX);
+    }
+
+    #[test]
+    fn optimized_loop_overflows_like_the_original() {
+        let program = parse("set x -9223372036854775803\nset c 10\nsub x 1\nsub c 1\njnz c -2");
+        let optimized = optimize(&program);
+        assert_eq!(optimized[2].opcode(), "submul");
+
+        let mut original = Processor::debug(&program);
+        let mut shortcut = Processor::debug(&optimized);
+        assert_eq!(original.execute(), shortcut.execute());
+        assert_eq!(original.program_counter(), 2);
+        assert_eq!(original.registers(), shortcut.registers());
+        assert_eq!(shortcut.get_register('x'), i64::MIN);
+    }
+}
@@ -1,6 +1,10 @@
 extern crate conflagration;
-use conflagration::{primes_up_to, Instruction, Processor};
-use conflagration::errors::Error;
+use conflagration::{optimize, Instruction, Processor};
+use conflagration::errors::{Error, RuntimeError};
+use std::env;
+use std::error;
+use std::fs::File;
+use std::io::BufWriter;
 use std::process::exit;
 
 fn main() {
@@ -10,15 +14,24 @@ fn main() {
     }
 }
 
-fn run() -> Result<(), Error> {
+fn run() -> Result<(), Box<dyn error::Error>> {
     let instructions = include_str!("puzzle_input.txt")
         .lines()
         .map(|line| line.parse::<Instruction>())
         .collect::<Result<Vec<Instruction>, Error>>()?;
 
+    let profile = env::args().any(|arg| arg == "--profile");
+    let trace_path = env::args().skip_while(|arg| arg != "--trace").nth(1);
+
     println!("Running in debug mode:");
-    let mut processor = Processor::debug();
-    processor.execute(&instructions);
+    let mut processor = Processor::debug(&instructions);
+    if let Some(path) = trace_path {
+        let trace = processor.trace()?;
+        trace.write(BufWriter::new(File::create(&path)?))?;
+        println!("Wrote {} steps to {}", trace.len(), path);
+    } else {
+        execute(&mut processor, profile)?;
+    }
 
     println!(
         "The multiply instruction was invoked {} times.",
@@ -27,29 +40,20 @@ fn run() -> Result<(), Error> {
     println!("h = {}", processor.get_register('h'));
 
     println!("Part 2:");
-    let h = part2();
-    println!("h = {}", h);
+    let optimized = optimize(&instructions);
+    let mut processor = Processor::release(&optimized);
+    execute(&mut processor, profile)?;
+    println!("h = {}", processor.get_register('h'));
     Ok(())
 }
 
-// The "assembly language" program is equivalent to:
-//
-// for b = 107900 to 124900 in steps of 17
-//     if b is not prime
-//        h = h + 1
-fn part2() -> usize {
-    let mut b = 107_900;
-    let c = 124_900;
-    let mut h = 0;
-    let primes = primes_up_to(c);
-
-    while b <= c {
-        if !primes.contains(&b) {
-            h += 1;
-        }
-
-        b += 17;
+fn execute(processor: &mut Processor, profile: bool) -> Result<(), RuntimeError> {
+    if profile {
+        let profile = processor.profile()?;
+        println!("{}", profile.report(processor.instructions()));
+    } else {
+        processor.execute()?;
     }
 
-    h
+    Ok(())
 }
@@ -0,0 +1,237 @@
+use super::{DivisorSearch, Instruction, Register, Value};
+
+// Replaces loops that the processor would take a very long time to run with
+// macro-instructions that have the same effect. Each macro-instruction takes
+// the place of the first instruction of its loop and the rest of the program
+// is left alone, so jump offsets stay valid and a macro-instruction can fall
+// back to running its loop one instruction at a time if it needs to.
+pub fn optimize(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut optimized = instructions.to_vec();
+
+    for index in 0..instructions.len() {
+        let remaining = &instructions[index..];
+        let replacement = test_composite(remaining)
+            .or_else(|| test_divisor(remaining).map(Instruction::TestDivisor))
+            .or_else(|| subtract_product(remaining));
+
+        if let Some(replacement) = replacement {
+            optimized[index] = replacement;
+        }
+    }
+
+    optimized
+}
+
+fn all_different(registers: &[Register]) -> bool {
+    registers
+        .iter()
+        .enumerate()
+        .all(|(n, r)| !registers[n + 1..].contains(r))
+}
+
+// sub x v
+// sub c 1
+// jnz c -2
+fn subtract_product(instructions: &[Instruction]) -> Option<Instruction> {
+    match *instructions.get(0..3)? {
+        [Instruction::Subtract(x, ref value), Instruction::Subtract(c, Value::Literal(1)), Instruction::JumpIfNotZero(Value::FromRegister(c2), Value::Literal(-2))]
+            if c == c2 && x != c && *value != Value::FromRegister(x)
+                && *value != Value::FromRegister(c) =>
+        {
+            Some(Instruction::SubtractProduct(x, value.clone(), c))
+        }
+        _ => None,
+    }
+}
+
+// set g d
+// mul g e
+// sub g b
+// jnz g 2
+// set f 0
+// sub e -1
+// set g e
+// sub g b
+// jnz g -8
+fn test_divisor(instructions: &[Instruction]) -> Option<DivisorSearch> {
+    use super::Instruction::*;
+    use super::Value::*;
+
+    match *instructions.get(0..9)? {
+        [Set(g, FromRegister(d)), Multiply(g2, FromRegister(e)), Subtract(g3, FromRegister(b)), JumpIfNotZero(FromRegister(g4), Literal(2)), Set(f, Literal(0)), Subtract(e2, Literal(-1)), Set(g5, FromRegister(e3)), Subtract(g6, FromRegister(b2)), JumpIfNotZero(FromRegister(g7), Literal(-8))]
+            if [g2, g3, g4, g5, g6, g7].iter().all(|r| *r == g) && e == e2 && e == e3
+                && b == b2 && all_different(&[f, d, e, b, g]) =>
+        {
+            Some(DivisorSearch {
+                flag: f,
+                divisor: d,
+                cofactor: e,
+                target: b,
+                scratch: g,
+            })
+        }
+        _ => None,
+    }
+}
+
+// set e n
+// (the divisor test loop)
+// sub d -1
+// set g d
+// sub g b
+// jnz g -13
+fn test_composite(instructions: &[Instruction]) -> Option<Instruction> {
+    use super::Instruction::*;
+    use super::Value::*;
+
+    let search = test_divisor(instructions.get(1..)?)?;
+
+    let start = match instructions[0] {
+        Set(e, Literal(start)) if e == search.cofactor => start,
+        _ => return None,
+    };
+
+    match *instructions.get(10..14)? {
+        [Subtract(d, Literal(-1)), Set(g, FromRegister(d2)), Subtract(g2, FromRegister(b)), JumpIfNotZero(FromRegister(g3), Literal(-13))]
+            if d == search.divisor && d == d2 && b == search.target && g == search.scratch
+                && g == g2 && g == g3 =>
+        {
+            Some(TestComposite(search, start))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Processor;
+
+    fn parse(program: &str) -> Vec<Instruction> {
+        program.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    fn run<'a>(program: &'a [Instruction], registers: &[(Register, i64)]) -> Processor<'a> {
+        let mut processor = Processor::debug(program);
+        for &(register, value) in registers {
+            processor.set_register(register, value);
+        }
+        processor.execute().unwrap();
+        processor
+    }
+
+    // Runs a program before and after optimization and checks that it has the
+    // same effect.
+    fn assert_equivalent(program: &[Instruction], registers: &[(Register, i64)]) {
+        let optimized = optimize(program);
+        assert_ne!(optimized, program, "nothing was optimized");
+
+        let expected = run(program, registers);
+        let actual = run(&optimized, registers);
+        assert_eq!(actual.registers(), expected.registers());
+        assert_eq!(actual.multiplication_count(), expected.multiplication_count());
+    }
+
+    #[test]
+    fn optimize_subtract_product() {
+        let program = parse("set a 3\nsub b -5\nsub c 1\njnz c -2\nsub a 1");
+        assert_eq!(
+            optimize(&program)[1],
+            Instruction::SubtractProduct('b', Value::Literal(-5), 'c')
+        );
+        assert_equivalent(&program, &[('c', 7)]);
+    }
+
+    #[test]
+    fn leave_loops_that_reuse_registers() {
+        let program = parse("sub c -5\nsub c 1\njnz c -2");
+        assert_eq!(optimize(&program), program);
+
+        let program = parse("sub b c\nsub c 1\njnz c -2");
+        assert_eq!(optimize(&program), program);
+    }
+
+    const COMPOSITE: &str = "set f 1
+set d 2
+set e 2
+set g d
+mul g e
+sub g b
+jnz g 2
+set f 0
+sub e -1
+set g e
+sub g b
+jnz g -8
+sub d -1
+set g d
+sub g b
+jnz g -13";
+
+    #[test]
+    fn optimize_composite_test() {
+        let program = parse(COMPOSITE);
+        let optimized = optimize(&program);
+        let search = DivisorSearch {
+            flag: 'f',
+            divisor: 'd',
+            cofactor: 'e',
+            target: 'b',
+            scratch: 'g',
+        };
+
+        assert_eq!(optimized[2], Instruction::TestComposite(search, 2));
+        assert_eq!(optimized[3], Instruction::TestDivisor(search));
+        assert_eq!(&optimized[4..], &program[4..]);
+    }
+
+    #[test]
+    fn composite_test_has_same_effect() {
+        let program = parse(COMPOSITE);
+        for b in 3..40 {
+            assert_equivalent(&program, &[('b', b)]);
+        }
+    }
+
+    #[test]
+    fn divisor_test_has_same_effect() {
+        let program = parse(&COMPOSITE.lines().skip(3).take(9).collect::<Vec<_>>().join("\n"));
+        for &(d, e, b) in &[(3, 2, 12), (3, 5, 12), (1, 2, 7), (0, 1, 5), (4, 4, 16)] {
+            assert_equivalent(&program, &[('d', d), ('e', e), ('b', b), ('f', 1)]);
+        }
+    }
+
+    #[test]
+    fn macros_fall_back_outside_their_range() {
+        let program = parse(COMPOSITE);
+        let optimized = optimize(&program);
+
+        // With b = -1 the loops start past their target and would run for an
+        // extremely long time, so compare the two versions after a fixed
+        // number of steps instead.
+        let mut expected = Processor::debug(&program);
+        expected.set_register('b', -1);
+        let mut actual = Processor::debug(&optimized);
+        actual.set_register('b', -1);
+
+        for _ in 0..50 {
+            expected.step().unwrap();
+            actual.step().unwrap();
+        }
+
+        assert_eq!(actual.program_counter(), expected.program_counter());
+        assert_eq!(actual.registers(), expected.registers());
+    }
+
+    #[test]
+    fn has_factors() {
+        use super::super::has_factors;
+        assert!(has_factors(12, 2, 2));
+        assert!(has_factors(12, 4, 2));
+        assert!(!has_factors(12, 7, 2));
+        assert!(!has_factors(13, 2, 2));
+        assert!(!has_factors(13, 1, 1));
+        assert!(has_factors(49, 7, 7));
+        assert!(!has_factors(49, 8, 2));
+    }
+}
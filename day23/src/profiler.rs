@@ -0,0 +1,199 @@
+use super::Instruction;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// A loop formed by a `jnz` that jumps backwards, from `end` to `start`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct HotLoop {
+    pub start: usize,
+    pub end: usize,
+    pub iterations: u64,
+    // Instructions executed within start..=end, including those from any
+    // loops nested inside this one.
+    pub instructions_executed: u64,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Profile {
+    instruction_counts: Vec<u64>,
+    opcode_counts: BTreeMap<&'static str, u64>,
+    back_edges: BTreeMap<(usize, usize), u64>,
+    total: u64,
+}
+
+impl Profile {
+    pub fn new(instruction_count: usize) -> Profile {
+        Profile {
+            instruction_counts: vec![0; instruction_count],
+            opcode_counts: BTreeMap::new(),
+            back_edges: BTreeMap::new(),
+            total: 0,
+        }
+    }
+
+    // Notes that the instruction at `index` was executed and that execution
+    // continued at `next`.
+    pub fn record(&mut self, instructions: &[Instruction], index: usize, next: usize) {
+        self.instruction_counts[index] += 1;
+        *self.opcode_counts
+            .entry(instructions[index].opcode())
+            .or_insert(0) += 1;
+        self.total += 1;
+
+        if next <= index {
+            *self.back_edges.entry((index, next)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn instruction_counts(&self) -> &[u64] {
+        &self.instruction_counts
+    }
+
+    pub fn opcode_count(&self, opcode: &str) -> u64 {
+        *self.opcode_counts.get(opcode).unwrap_or(&0)
+    }
+
+    // Backward jumps that were taken, most executed loop first.
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<HotLoop> = self.back_edges
+            .iter()
+            .map(|(&(end, start), &iterations)| HotLoop {
+                start,
+                end,
+                iterations,
+                instructions_executed: self.instruction_counts[start..=end].iter().sum(),
+            })
+            .collect();
+
+        loops.sort_by(|a, b| {
+            b.instructions_executed
+                .cmp(&a.instructions_executed)
+                .then(a.start.cmp(&b.start))
+        });
+
+        loops
+    }
+
+    pub fn report(&self, instructions: &[Instruction]) -> String {
+        let mut report = String::new();
+        let percent = |count: u64| {
+            if self.total == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / self.total as f64
+            }
+        };
+
+        writeln!(report, "{} instructions executed", self.total).unwrap();
+
+        writeln!(report, "\nBy opcode:").unwrap();
+        for (opcode, &count) in &self.opcode_counts {
+            writeln!(report, "  {:<6} {:>12} {:>6.2}%", opcode, count, percent(count)).unwrap();
+        }
+
+        writeln!(report, "\nBy instruction:").unwrap();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let count = self.instruction_counts[index];
+            writeln!(
+                report,
+                "  {:>4}: {:<16} {:>12} {:>6.2}%",
+                index,
+                instruction.to_string(),
+                count,
+                percent(count)
+            ).unwrap();
+        }
+
+        writeln!(report, "\nHot loops:").unwrap();
+        let loops = self.hot_loops();
+        if loops.is_empty() {
+            writeln!(report, "  none").unwrap();
+        }
+        for hot_loop in loops {
+            writeln!(
+                report,
+                "  {:>4}..{:<4} {:>12} iterations {:>12} instructions {:>6.2}%",
+                hot_loop.start,
+                hot_loop.end,
+                hot_loop.iterations,
+                hot_loop.instructions_executed,
+                percent(hot_loop.instructions_executed)
+            ).unwrap();
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Processor, Value};
+    use super::*;
+
+    // Two nested loops: the inner one runs three times per outer iteration,
+    // and the outer one runs twice.
+    fn nested_loops() -> Vec<Instruction> {
+        vec![
+            Instruction::Set('a', Value::Literal(2)),
+            Instruction::Set('b', Value::Literal(3)),
+            Instruction::Multiply('c', Value::Literal(1)),
+            Instruction::Subtract('b', Value::Literal(1)),
+            Instruction::JumpIfNotZero(Value::FromRegister('b'), Value::Literal(-2)),
+            Instruction::Subtract('a', Value::Literal(1)),
+            Instruction::JumpIfNotZero(Value::FromRegister('a'), Value::Literal(-5)),
+        ]
+    }
+
+    #[test]
+    fn counts_instructions_and_opcodes() {
+        let program = nested_loops();
+        let profile = Processor::debug(&program).profile().unwrap();
+
+        assert_eq!(profile.instruction_counts(), &[1, 2, 6, 6, 6, 2, 2]);
+        assert_eq!(profile.total(), 25);
+        assert_eq!(profile.opcode_count("set"), 3);
+        assert_eq!(profile.opcode_count("sub"), 8);
+        assert_eq!(profile.opcode_count("mul"), 6);
+        assert_eq!(profile.opcode_count("jnz"), 8);
+    }
+
+    #[test]
+    fn finds_nested_loops() {
+        let program = nested_loops();
+        let profile = Processor::debug(&program).profile().unwrap();
+
+        assert_eq!(
+            profile.hot_loops(),
+            vec![
+                HotLoop {
+                    start: 1,
+                    end: 6,
+                    iterations: 1,
+                    instructions_executed: 24,
+                },
+                HotLoop {
+                    start: 2,
+                    end: 4,
+                    iterations: 4,
+                    instructions_executed: 18,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn profiling_matches_execute() {
+        let program = nested_loops();
+        let mut profiled = Processor::debug(&program);
+        profiled.profile().unwrap();
+        let mut executed = Processor::debug(&program);
+        executed.execute().unwrap();
+
+        assert_eq!(profiled.registers(), executed.registers());
+        assert_eq!(profiled.multiplication_count(), executed.multiplication_count());
+    }
+}
@@ -0,0 +1,947 @@
+use super::expression::{Atom, Condition, Expr, Variable};
+use super::{DivisorSearch, Instruction, Register, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SymbolicErrorKind {
+    // A jump by an amount that depends on the program's input.
+    ComputedJump,
+    // A backward jump that does not close a loop, or loops that overlap
+    // without one being inside the other.
+    IrregularLoop,
+    MultipleExits,
+    // A register that changes from one iteration of a loop to the next in a
+    // way that cannot be summarized.
+    UnsupportedRegister(Register),
+    // A loop whose exit condition is not linear in the number of iterations.
+    IrregularExit,
+    NeverExits,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct SymbolicError {
+    pub kind: SymbolicErrorKind,
+    pub index: usize,
+}
+
+impl SymbolicError {
+    fn new(kind: SymbolicErrorKind, index: usize) -> SymbolicError {
+        SymbolicError { kind, index }
+    }
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SymbolicErrorKind::ComputedJump => write!(f, "Jump by a register value")?,
+            SymbolicErrorKind::IrregularLoop => write!(f, "Irregular loop")?,
+            SymbolicErrorKind::MultipleExits => write!(f, "Loop with more than one exit")?,
+            SymbolicErrorKind::UnsupportedRegister(r) => {
+                write!(f, "Cannot summarize how register '{}' changes in the loop", r)?
+            }
+            SymbolicErrorKind::IrregularExit => {
+                write!(f, "Cannot count the iterations of the loop")?
+            }
+            SymbolicErrorKind::NeverExits => write!(f, "Loop never exits")?,
+        }
+
+        write!(f, " at instruction {}", self.index)
+    }
+}
+
+impl error::Error for SymbolicError {
+    fn description(&self) -> &str {
+        "Symbolic execution failed"
+    }
+}
+
+// One way through a program: the registers when it finishes, provided that
+// each condition is nonzero or zero as recorded, and that every assumption
+// is at least zero. The assumptions are what the loop summaries need to
+// hold, such as a loop counter starting below its limit.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Path {
+    pub conditions: Vec<(Expr, bool)>,
+    pub assumptions: Vec<Expr>,
+    pub registers: BTreeMap<Register, Expr>,
+}
+
+// Runs a program with the given starting registers, any of which may be
+// symbolic, and every other register zero. Each loop is replaced by a
+// closed form for its effect, and the program is split into separate paths
+// wherever it branches on a condition that is not known.
+pub fn execute_symbolically(
+    instructions: &[Instruction],
+    registers: &[(Register, Expr)],
+) -> Result<Vec<Path>, SymbolicError> {
+    let mut executor = Executor::new(instructions)?;
+    let state = State {
+        registers: registers.iter().cloned().collect(),
+        assumptions: BTreeSet::new(),
+    };
+
+    let mut paths = Vec::new();
+    executor.paths(0, state, Vec::new(), &mut paths)?;
+    Ok(paths)
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    registers: BTreeMap<Register, Expr>,
+    assumptions: BTreeSet<Expr>,
+}
+
+impl State {
+    fn get(&self, register: Register) -> Expr {
+        self.registers.get(&register).cloned().unwrap_or_default()
+    }
+
+    fn set(&mut self, register: Register, value: Expr) {
+        self.registers.insert(register, value);
+    }
+
+    fn value(&self, value: &Value) -> Expr {
+        match *value {
+            Value::FromRegister(r) => self.get(r),
+            Value::Literal(n) => Expr::constant(n),
+        }
+    }
+
+    // Records that `expr` must not be negative for the program to get this
+    // far.
+    fn assume(&mut self, expr: Expr, index: usize) -> Result<(), SymbolicError> {
+        match expr.as_constant() {
+            Some(n) if n < 0 => Err(SymbolicError::new(SymbolicErrorKind::NeverExits, index)),
+            Some(_) => Ok(()),
+            None => {
+                self.assumptions.insert(expr);
+                Ok(())
+            }
+        }
+    }
+}
+
+// The state after the fall-through branch of a jump on `condition`, which
+// runs only when the condition is zero, has been merged with the state when
+// the jump was taken.
+fn merge(condition: &Expr, taken: State, fallen_through: State) -> State {
+    let when = Expr::indicator(Condition::Zero(condition.clone()));
+    let mut merged = taken.clone();
+
+    let registers: BTreeSet<Register> = taken
+        .registers
+        .keys()
+        .chain(fallen_through.registers.keys())
+        .cloned()
+        .collect();
+    for r in registers {
+        let difference = fallen_through.get(r) - taken.get(r);
+        merged.set(r, taken.get(r) + when.clone() * difference);
+    }
+
+    for assumption in fallen_through.assumptions.difference(&taken.assumptions) {
+        merged.assumptions.insert(when.clone() * assumption.clone());
+    }
+
+    merged
+}
+
+enum Step {
+    Next(usize),
+    // A jump to the target if the condition, which is not known, is nonzero.
+    Branch(Expr, usize),
+}
+
+enum Flow {
+    Continue(State),
+    // Jumped out of the region to the given index.
+    Exit(usize, State),
+}
+
+// Leaves a region when `when_zero` is zero.
+struct ConditionalExit {
+    when_zero: Expr,
+    target: usize,
+    state: State,
+}
+
+struct Region {
+    flow: Flow,
+    exits: Vec<ConditionalExit>,
+}
+
+// How a register changes on each iteration of a loop.
+enum Form {
+    // By the same amount every time.
+    Induction { start: Expr, step: Expr },
+    // To a value that depends only on induction registers and registers the
+    // loop leaves alone.
+    Overwrite { start: Expr, update: Expr },
+    // To `value` on iterations where `condition`, which is 0 or 1, is 1.
+    SetIf {
+        start: Expr,
+        condition: Expr,
+        value: Expr,
+    },
+    // By an amount that depends only on induction registers.
+    Accumulate { start: Expr, delta: Expr },
+}
+
+struct Summary {
+    // Stands for each register the loop writes, at the start of an
+    // iteration.
+    symbols: BTreeMap<Register, Variable>,
+    forms: BTreeMap<Register, Form>,
+    // An induction register that counts up by one, which makes the
+    // iterations easier to read when indexed by its value.
+    counter: Option<Register>,
+}
+
+impl Summary {
+    // Replaces each induction register's symbol in `expr` with its value
+    // after `iterations` iterations.
+    fn at(&self, expr: &Expr, iterations: &Expr) -> Expr {
+        let mut values = HashMap::new();
+        for (r, v) in &self.symbols {
+            if let Some(Form::Induction { start, step }) = self.forms.get(r)
+            {
+                values.insert(v.id, start.clone() + step.clone() * iterations.clone());
+            }
+        }
+
+        expr.substitute(&mut |atom| match *atom {
+            Atom::Variable(ref v) => values.get(&v.id).cloned(),
+            _ => None,
+        })
+    }
+}
+
+// The registers an instruction may change.
+fn written(instruction: &Instruction) -> Vec<Register> {
+    match *instruction {
+        Instruction::Set(r, _) | Instruction::Subtract(r, _) | Instruction::Multiply(r, _) => {
+            vec![r]
+        }
+        Instruction::JumpIfNotZero(_, _) => vec![],
+        Instruction::SubtractProduct(x, _, c) => vec![x, c],
+        Instruction::TestDivisor(ref search) => vec![search.flag, search.cofactor, search.scratch],
+        Instruction::TestComposite(ref search, _) => {
+            vec![search.flag, search.divisor, search.cofactor, search.scratch]
+        }
+    }
+}
+
+fn is_macro(instruction: &Instruction) -> bool {
+    matches!(
+        *instruction,
+        Instruction::SubtractProduct(_, _, _)
+            | Instruction::TestDivisor(_)
+            | Instruction::TestComposite(_, _)
+    )
+}
+
+// Finds every loop, as a map from its first instruction to the backward jump
+// at its end.
+fn find_loops(instructions: &[Instruction]) -> Result<BTreeMap<usize, usize>, SymbolicError> {
+    let mut loops = BTreeMap::new();
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        if let Instruction::JumpIfNotZero(ref condition, Value::Literal(offset)) = *instruction {
+            if *condition == Value::Literal(0) || offset > 0 {
+                continue;
+            }
+
+            let header = match (index as i64).checked_add(offset) {
+                Some(header) if header >= 0 => header as usize,
+                _ => continue,
+            };
+            if loops.insert(header, index).is_some() {
+                return Err(SymbolicError::new(SymbolicErrorKind::IrregularLoop, index));
+            }
+        }
+    }
+
+    for (&header, &end) in &loops {
+        for (_, &other_end) in loops.range(header + 1..=end) {
+            if other_end > end {
+                return Err(SymbolicError::new(SymbolicErrorKind::IrregularLoop, other_end));
+            }
+        }
+    }
+
+    Ok(loops)
+}
+
+// If `condition`, which is 0 or 1, multiplied by -1 times the register's
+// symbol plus `value` is its change, returns the condition and value.
+fn set_if(delta: &Expr, symbol: &Variable, others: &BTreeSet<usize>) -> Option<(Expr, Expr)> {
+    let (alpha, beta) = delta.split_linear(symbol)?;
+    let condition = -beta;
+    if alpha.mentions_any(others) || condition.mentions_any(others)
+        || condition.clone() * condition.clone() != condition
+    {
+        return None;
+    }
+
+    let value = alpha.factor_out(&condition)?;
+    if value.clone() * condition.clone() != alpha {
+        return None;
+    }
+
+    Some((condition, value))
+}
+
+struct Executor<'a> {
+    instructions: &'a [Instruction],
+    loops: BTreeMap<usize, usize>,
+    next_id: usize,
+    // How many bound variables have been named after each letter.
+    names: HashMap<char, usize>,
+}
+
+impl<'a> Executor<'a> {
+    fn new(instructions: &'a [Instruction]) -> Result<Executor<'a>, SymbolicError> {
+        Ok(Executor {
+            instructions,
+            loops: find_loops(instructions)?,
+            next_id: 0,
+            names: HashMap::new(),
+        })
+    }
+
+    fn fresh(&mut self, name: String) -> Variable {
+        self.next_id += 1;
+        Variable {
+            id: self.next_id,
+            name,
+        }
+    }
+
+    // A variable for a sum or condition to range over, named after a
+    // register, or k if it just counts iterations.
+    fn bound(&mut self, base: char) -> Variable {
+        let primes = {
+            let count = self.names.entry(base).or_insert(0);
+            *count += 1;
+            *count
+        };
+        self.fresh(format!("{}{}", base, "'".repeat(primes)))
+    }
+
+    // Where a jump by `offset` from `index` goes. Anywhere outside the
+    // program ends it.
+    fn target(&self, index: usize, offset: i64) -> usize {
+        match (index as i64).checked_add(offset) {
+            Some(target) if target >= 0 && target < self.instructions.len() as i64 => {
+                target as usize
+            }
+            _ => self.instructions.len(),
+        }
+    }
+
+    // Executes the instruction at `index`, unless it is a jump on a
+    // condition that is not known.
+    fn step(&mut self, index: usize, state: &mut State) -> Result<Step, SymbolicError> {
+        match self.instructions[index] {
+            Instruction::Set(r, ref v) => {
+                let value = state.value(v);
+                state.set(r, value);
+            }
+            Instruction::Subtract(r, ref v) => {
+                let value = state.get(r) - state.value(v);
+                state.set(r, value);
+            }
+            Instruction::Multiply(r, ref v) => {
+                let value = state.get(r) * state.value(v);
+                state.set(r, value);
+            }
+            Instruction::JumpIfNotZero(ref condition, ref offset) => {
+                let condition = state.value(condition);
+                if condition.as_constant() == Some(0) {
+                    return Ok(Step::Next(index + 1));
+                }
+
+                let target = match state.value(offset).as_constant() {
+                    Some(offset) => self.target(index, offset),
+                    None => {
+                        return Err(SymbolicError::new(SymbolicErrorKind::ComputedJump, index))
+                    }
+                };
+
+                return Ok(match condition.as_constant() {
+                    Some(_) => Step::Next(target),
+                    None => Step::Branch(condition, target),
+                });
+            }
+            Instruction::SubtractProduct(x, ref v, c) => {
+                let value = state.get(x) - state.value(v) * state.get(c);
+                state.set(x, value);
+                state.set(c, Expr::zero());
+                return Ok(Step::Next(index + 3));
+            }
+            Instruction::TestDivisor(ref search) => {
+                let cofactor = state.get(search.cofactor);
+                let divisor = state.get(search.divisor);
+                let found = self.divisor_found(search, divisor, cofactor.clone(), state);
+                let remaining = state.get(search.target) - cofactor - Expr::constant(1);
+                state.assume(remaining, index)?;
+                self.finish_search(search, found, state);
+                return Ok(Step::Next(index + 9));
+            }
+            Instruction::TestComposite(ref search, start) => {
+                let target = state.get(search.target);
+                let from = state.get(search.divisor);
+                let d = self.bound(search.divisor);
+                let cofactor = Expr::constant(start);
+                let found = self.divisor_found(search, Expr::variable(&d), cofactor, state);
+                let last = target.clone() - Expr::constant(1);
+                let found = Expr::exists(d, from.clone(), last, found);
+
+                state.assume(target.clone() - from - Expr::constant(1), index)?;
+                state.assume(target.clone() - Expr::constant(start + 1), index)?;
+                self.finish_search(search, found, state);
+                state.set(search.divisor, target);
+                return Ok(Step::Next(index + 14));
+            }
+        }
+
+        Ok(Step::Next(index + 1))
+    }
+
+    // Whether divisor * e == target for some e from `cofactor` up to the
+    // target.
+    fn divisor_found(
+        &mut self,
+        search: &DivisorSearch,
+        divisor: Expr,
+        cofactor: Expr,
+        state: &State,
+    ) -> Expr {
+        let target = state.get(search.target);
+        let e = self.bound(search.cofactor);
+        let product = divisor * Expr::variable(&e) - target.clone();
+        Expr::exists(
+            e,
+            cofactor,
+            target - Expr::constant(1),
+            Expr::indicator(Condition::Zero(product)),
+        )
+    }
+
+    fn finish_search(&self, search: &DivisorSearch, found: Expr, state: &mut State) {
+        let flag = state.get(search.flag);
+        state.set(search.flag, flag.clone() - found * flag);
+        let target = state.get(search.target);
+        state.set(search.cofactor, target);
+        state.set(search.scratch, Expr::zero());
+    }
+
+    // The end of the loop that starts at `index`, if it should be summarized
+    // rather than stepped into. A macro-instruction at the start of a loop
+    // does the work of the whole loop itself.
+    fn loop_at(&self, index: usize) -> Option<usize> {
+        if is_macro(&self.instructions[index]) {
+            None
+        } else {
+            self.loops.get(&index).cloned()
+        }
+    }
+
+    // Follows every path from `index` to the end of the program.
+    fn paths(
+        &mut self,
+        mut index: usize,
+        mut state: State,
+        mut conditions: Vec<(Expr, bool)>,
+        paths: &mut Vec<Path>,
+    ) -> Result<(), SymbolicError> {
+        while index < self.instructions.len() {
+            if let Some(end) = self.loop_at(index) {
+                let (after, target) = self.summarize(index, end, state)?;
+                state = after;
+                index = target;
+                continue;
+            }
+
+            let (condition, target) = match self.step(index, &mut state)? {
+                Step::Next(next) if next > index => {
+                    index = next;
+                    continue;
+                }
+                Step::Branch(condition, target) if target > index => (condition, target),
+                _ => return Err(SymbolicError::new(SymbolicErrorKind::IrregularLoop, index)),
+            };
+
+            match conditions.iter().find(|c| c.0 == condition).map(|c| c.1) {
+                Some(true) => index = target,
+                Some(false) => index += 1,
+                None => {
+                    // Both paths should name their variables the same way.
+                    let names = self.names.clone();
+                    let mut taken = conditions.clone();
+                    taken.push((condition.clone(), true));
+                    self.paths(target, state.clone(), taken, paths)?;
+                    self.names = names;
+
+                    conditions.push((condition, false));
+                    index += 1;
+                }
+            }
+        }
+
+        paths.push(Path {
+            conditions,
+            assumptions: state.assumptions.into_iter().collect(),
+            registers: state.registers,
+        });
+        Ok(())
+    }
+
+    // Runs the instructions from `start` up to `end`, merging the states
+    // where they branch. `in_loop` is set if they are the body of the loop
+    // that starts at `start`.
+    fn region(
+        &mut self,
+        start: usize,
+        end: usize,
+        mut state: State,
+        in_loop: bool,
+    ) -> Result<Region, SymbolicError> {
+        let mut exits = Vec::new();
+        let mut index = start;
+
+        loop {
+            if index == end {
+                return Ok(Region {
+                    flow: Flow::Continue(state),
+                    exits,
+                });
+            } else if index < start || index > end {
+                return Ok(Region {
+                    flow: Flow::Exit(index, state),
+                    exits,
+                });
+            }
+
+            if let Some(loop_end) = self.loop_at(index) {
+                if !(in_loop && index == start) {
+                    if loop_end >= end {
+                        return Err(SymbolicError::new(SymbolicErrorKind::IrregularLoop, index));
+                    }
+
+                    let (after, target) = self.summarize(index, loop_end, state)?;
+                    state = after;
+                    index = target;
+                    continue;
+                }
+            }
+
+            let (condition, target) = match self.step(index, &mut state)? {
+                Step::Next(next) if next > index => {
+                    index = next;
+                    continue;
+                }
+                Step::Branch(condition, target) if target > index => (condition, target),
+                _ => return Err(SymbolicError::new(SymbolicErrorKind::IrregularLoop, index)),
+            };
+
+            if target > end {
+                exits.push(ConditionalExit {
+                    when_zero: Expr::indicator(Condition::Zero(condition)),
+                    target,
+                    state: state.clone(),
+                });
+                index += 1;
+                continue;
+            }
+
+            let block = self.region(index + 1, target, state.clone(), false)?;
+            if !block.exits.is_empty() {
+                return Err(SymbolicError::new(SymbolicErrorKind::IrregularExit, index));
+            }
+
+            match block.flow {
+                Flow::Continue(fallen_through) => state = merge(&condition, state, fallen_through),
+                Flow::Exit(target, exited) => exits.push(ConditionalExit {
+                    when_zero: condition,
+                    target,
+                    state: exited,
+                }),
+            }
+            index = target;
+        }
+    }
+
+    // Works out the effect of the loop from `header` to the backward jump at
+    // `end`, and returns the state when it exits along with where it goes.
+    fn summarize(
+        &mut self,
+        header: usize,
+        end: usize,
+        entry: State,
+    ) -> Result<(State, usize), SymbolicError> {
+        let error = |kind| SymbolicError::new(kind, header);
+
+        // Run the body once, starting from symbols for everything it
+        // changes.
+        let registers: BTreeSet<Register> = self.instructions[header..=end]
+            .iter()
+            .flat_map(written)
+            .collect();
+        let mut symbols = BTreeMap::new();
+        let mut start = entry.clone();
+        start.assumptions.clear();
+        for r in registers {
+            let symbol = self.fresh(r.to_ascii_uppercase().to_string());
+            start.set(r, Expr::variable(&symbol));
+            symbols.insert(r, symbol);
+        }
+        let ids: BTreeSet<usize> = symbols.values().map(|v| v.id).collect();
+
+        let region = self.region(header, end, start, true)?;
+        let body = match region.flow {
+            Flow::Continue(body) => body,
+            Flow::Exit(_, _) => return Err(error(SymbolicErrorKind::IrregularLoop)),
+        };
+
+        let mut exits = region.exits;
+        if let Instruction::JumpIfNotZero(ref condition, _) = self.instructions[end] {
+            let condition = body.value(condition);
+            if condition.as_constant().is_none_or(|n| n == 0) {
+                exits.push(ConditionalExit {
+                    when_zero: condition,
+                    target: end + 1,
+                    state: body.clone(),
+                });
+            }
+        }
+        let exit = match exits.len() {
+            0 => return Err(error(SymbolicErrorKind::NeverExits)),
+            1 => exits.remove(0),
+            _ => return Err(error(SymbolicErrorKind::MultipleExits)),
+        };
+
+        // Classify each register by how it changes, starting with the
+        // inductions that everything else may depend on.
+        let mut forms = BTreeMap::new();
+        let mut inductions = BTreeSet::new();
+        for (&r, symbol) in &symbols {
+            let step = body.get(r) - Expr::variable(symbol);
+            if !step.mentions_any(&ids) {
+                inductions.insert(symbol.id);
+                forms.insert(
+                    r,
+                    Form::Induction {
+                        start: entry.get(r),
+                        step,
+                    },
+                );
+            }
+        }
+
+        let others: BTreeSet<usize> = ids.difference(&inductions).cloned().collect();
+        for (&r, symbol) in &symbols {
+            if forms.contains_key(&r) {
+                continue;
+            }
+
+            let start = entry.get(r);
+            let update = body.get(r);
+            let delta = update.clone() - Expr::variable(symbol);
+            let form = if !update.mentions_any(&others) {
+                Form::Overwrite { start, update }
+            } else if !delta.mentions_any(&others) {
+                Form::Accumulate { start, delta }
+            } else {
+                match set_if(&delta, symbol, &others) {
+                    Some((condition, ref value)) if !value.mentions_any(&ids) => Form::SetIf {
+                        start,
+                        condition,
+                        value: value.clone(),
+                    },
+                    _ => return Err(error(SymbolicErrorKind::UnsupportedRegister(r))),
+                }
+            };
+            forms.insert(r, form);
+        }
+
+        let counter = {
+            let counters: Vec<Register> = forms
+                .iter()
+                .filter(|&(_, form)| match *form {
+                    Form::Induction { ref step, .. } => step.as_constant() == Some(1),
+                    _ => false,
+                })
+                .map(|(&r, _)| r)
+                .collect();
+            counters
+                .iter()
+                .find(|r| exit.when_zero.mentions(&symbols[r]))
+                .or_else(|| counters.first())
+                .cloned()
+        };
+        let summary = Summary {
+            symbols,
+            forms,
+            counter,
+        };
+
+        // Count the iterations before the one in which the loop exits.
+        let j = self.fresh("j".to_string());
+        let iteration = Expr::variable(&j);
+        let condition = summary.at(&exit.when_zero, &iteration);
+        if condition.mentions_any(&ids) {
+            return Err(error(SymbolicErrorKind::IrregularExit));
+        }
+        let (alpha, beta) = condition
+            .split_linear(&j)
+            .ok_or_else(|| error(SymbolicErrorKind::IrregularExit))?;
+
+        let mut result = entry;
+        let count = match (alpha.as_constant(), beta.as_constant()) {
+            (Some(0), Some(0)) => Expr::zero(),
+            (Some(_), Some(0)) => return Err(error(SymbolicErrorKind::NeverExits)),
+            (Some(a), Some(b)) if a % b == 0 && -a / b >= 0 => Expr::constant(-a / b),
+            (Some(_), Some(_)) => return Err(error(SymbolicErrorKind::NeverExits)),
+            (_, Some(b)) if b == 1 || b == -1 => alpha.scale(-b),
+            _ => return Err(error(SymbolicErrorKind::IrregularExit)),
+        };
+        result.assume(count.clone(), header)?;
+
+        // Conditions that had to hold in every iteration are linear in the
+        // iteration, so only need to hold in the first and last.
+        for assumption in body.assumptions.union(&exit.state.assumptions) {
+            let assumption = summary.at(assumption, &iteration);
+            if assumption.mentions_any(&ids) {
+                return Err(error(SymbolicErrorKind::IrregularLoop));
+            }
+
+            let (alpha, beta) = assumption
+                .split_linear(&j)
+                .ok_or_else(|| error(SymbolicErrorKind::IrregularLoop))?;
+            result.assume(alpha.clone(), header)?;
+            result.assume(alpha + beta * count.clone(), header)?;
+        }
+
+        // Registers that have their final values by the time the loop exits
+        // have the values from the end of the last full iteration. The rest
+        // are worked out from the values at the start of the iteration in
+        // which it exits.
+        let completed = count.clone() + Expr::constant(1);
+        let mut at_count = HashMap::new();
+        for &r in summary.symbols.keys() {
+            let exit_value = exit.state.get(r);
+            if exit_value == body.get(r) {
+                let value = self.value_after(&summary, r, &completed, &mut result, header)?;
+                result.set(r, value);
+            } else {
+                for (&s, v) in &summary.symbols {
+                    if exit_value.mentions(v) && !at_count.contains_key(&v.id) {
+                        let value = self.value_after(&summary, s, &count, &mut result, header)?;
+                        at_count.insert(v.id, value);
+                    }
+                }
+
+                let value = exit_value.substitute(&mut |atom| match *atom {
+                    Atom::Variable(ref v) => at_count.get(&v.id).cloned(),
+                    _ => None,
+                });
+                result.set(r, value);
+            }
+        }
+
+        Ok((result, exit.target))
+    }
+
+    // The value of a register after a loop has run `iterations` times.
+    fn value_after(
+        &mut self,
+        summary: &Summary,
+        register: Register,
+        iterations: &Expr,
+        state: &mut State,
+        index: usize,
+    ) -> Result<Expr, SymbolicError> {
+        Ok(match summary.forms[&register] {
+            Form::Induction {
+                ref start,
+                ref step,
+            } => start.clone() + step.clone() * iterations.clone(),
+            Form::Overwrite {
+                ref start,
+                ref update,
+            } => {
+                let previous = iterations.clone() - Expr::constant(1);
+                if previous.as_constant().is_some_and(|n| n < 0) {
+                    return Ok(start.clone());
+                }
+
+                state.assume(previous.clone(), index)?;
+                summary.at(update, &previous)
+            }
+            Form::SetIf {
+                ref start,
+                ref condition,
+                ref value,
+            } => {
+                let (variable, first, last, iteration) = self.iterations(summary, iterations);
+                let found = Expr::exists(variable, first, last, summary.at(condition, &iteration));
+                start.clone() + found * (value.clone() - start.clone())
+            }
+            Form::Accumulate {
+                ref start,
+                ref delta,
+            } => {
+                let (variable, first, last, iteration) = self.iterations(summary, iterations);
+                start.clone() + Expr::sum(variable, first, last, summary.at(delta, &iteration))
+            }
+        })
+    }
+
+    // A variable to range over the first `iterations` iterations of a loop,
+    // with its first and last values and the iteration it stands for. It is
+    // the value of the loop's counter if it has one.
+    fn iterations(
+        &mut self,
+        summary: &Summary,
+        iterations: &Expr,
+    ) -> (Variable, Expr, Expr, Expr) {
+        let (variable, first) = match summary.counter.map(|r| (r, &summary.forms[&r])) {
+            Some((r, Form::Induction { start, .. })) => (self.bound(r), start.clone()),
+            _ => (self.bound('k'), Expr::zero()),
+        };
+
+        let last = first.clone() + iterations.clone() - Expr::constant(1);
+        let iteration = Expr::variable(&variable) - first.clone();
+        (variable, first, last, iteration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{optimize, Processor};
+    use super::*;
+
+    fn parse(program: &str) -> Vec<Instruction> {
+        program.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    fn only_path(program: &[Instruction], registers: &[(Register, Expr)]) -> Path {
+        let mut paths = execute_symbolically(program, registers).unwrap();
+        assert_eq!(paths.len(), 1);
+        paths.remove(0)
+    }
+
+    fn evaluate(path: &Path, register: Register) -> Option<i64> {
+        path.registers.get(&register)?.evaluate(&BTreeMap::new())
+    }
+
+    fn error(program: &str) -> SymbolicError {
+        execute_symbolically(&parse(program), &[('a', Expr::input('a'))]).unwrap_err()
+    }
+
+    #[test]
+    fn fork_on_unknown_registers() {
+        let program = parse("jnz a 2\nset b 1\nset c 2");
+        let paths = execute_symbolically(&program, &[('a', Expr::input('a'))]).unwrap();
+        assert_eq!(paths.len(), 2);
+
+        assert_eq!(paths[0].conditions, vec![(Expr::input('a'), true)]);
+        assert_eq!(paths[0].registers.get(&'b'), None);
+        assert_eq!(paths[1].conditions, vec![(Expr::input('a'), false)]);
+        assert_eq!(paths[1].registers.get(&'b'), Some(&Expr::constant(1)));
+        assert!(paths.iter().all(|path| path.registers[&'c'] == Expr::constant(2)));
+    }
+
+    #[test]
+    fn summarize_counted_loop() {
+        let program = parse("sub c -3\nsub b 1\njnz b -2");
+
+        let path = only_path(&program, &[('b', Expr::constant(5))]);
+        assert_eq!(path.registers[&'b'], Expr::zero());
+        assert_eq!(path.registers[&'c'], Expr::constant(15));
+        assert!(path.assumptions.is_empty());
+
+        let b = Expr::input('b');
+        let path = only_path(&program, &[('b', b.clone())]);
+        assert_eq!(path.registers[&'b'], Expr::zero());
+        assert_eq!(path.registers[&'c'], b.scale(3));
+        assert_eq!(path.assumptions, vec![b - Expr::constant(1)]);
+    }
+
+    #[test]
+    fn merge_branches_inside_loops() {
+        // Counts the values of d from 9 down to 1 for which (d - 2)(d - 3) is
+        // zero.
+        let program = parse(
+            "set d 9\nset e d\nsub e 5\nmul e d\nsub e -6\njnz e 2\nsub h -1\nsub d 1\njnz d -7",
+        );
+        let path = only_path(&program, &[]);
+        assert_eq!(
+            path.registers[&'h'].to_string(),
+            "sum(k' in [0, 8]: [13*k' == k'*k' + 42])"
+        );
+
+        let mut processor = Processor::debug(&program);
+        processor.execute().unwrap();
+        assert_eq!(processor.get_register('h'), 2);
+        for (register, value) in processor.registers() {
+            assert_eq!(evaluate(&path, register), Some(value), "register {}", register);
+        }
+    }
+
+    #[test]
+    fn debug_run_matches_processor() {
+        let program = parse(include_str!("puzzle_input.txt"));
+        let path = only_path(&program, &[('a', Expr::zero())]);
+
+        let mut processor = Processor::debug(&program);
+        processor.execute().unwrap();
+        for (register, value) in processor.registers() {
+            assert_eq!(evaluate(&path, register), Some(value), "register {}", register);
+        }
+    }
+
+    #[test]
+    fn release_run_counts_composites() {
+        let program = parse(include_str!("puzzle_input.txt"));
+        let path = only_path(&program, &[('a', Expr::constant(1))]);
+        assert_eq!(path.registers[&'b'], Expr::constant(124_900));
+        assert_eq!(evaluate(&path, 'h'), Some(907));
+
+        let optimized = only_path(&optimize(&program), &[('a', Expr::constant(1))]);
+        for register in path.registers.keys() {
+            assert_eq!(evaluate(&optimized, *register), evaluate(&path, *register));
+        }
+    }
+
+    #[test]
+    fn reject_unsupported_programs() {
+        assert_eq!(error("jnz 1 a").kind, SymbolicErrorKind::ComputedJump);
+        // A jump far past the end is not an error: it just halts.
+        assert_eq!(
+            execute_symbolically(&parse("set a 1\njnz a 9223372036854775807"), &[])
+                .unwrap()[0]
+                .registers[&'a'],
+            Expr::constant(1)
+        );
+        assert_eq!(
+            error("set b 3\nmul c c\nsub b 1\njnz b -2"),
+            SymbolicError::new(SymbolicErrorKind::UnsupportedRegister('c'), 1)
+        );
+        assert_eq!(
+            error("set b 3\nsub b -1\njnz b -1"),
+            SymbolicError::new(SymbolicErrorKind::NeverExits, 1)
+        );
+        assert_eq!(
+            error("sub b 1\nsub c 1\njnz b -2\njnz c -2"),
+            SymbolicError::new(SymbolicErrorKind::IrregularLoop, 3)
+        );
+        assert_eq!(
+            error("set c 3\njnz a 3\nsub b 1\nsub c 1\njnz c -2"),
+            SymbolicError::new(SymbolicErrorKind::IrregularLoop, 4)
+        );
+    }
+}
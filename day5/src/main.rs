@@ -1,5 +1,7 @@
+extern crate register_vm;
 extern crate stopwatch;
 
+use register_vm::{Budget, Halted, Watchdog};
 use std::num::ParseIntError;
 use stopwatch::Stopwatch;
 
@@ -23,37 +25,45 @@ fn main() {
 }
 
 fn maze_part_1(offsets: &mut [i32]) -> usize {
-    let mut index: usize;
-    let mut next_index: i32 = 0;
-    let length = offsets.len() as i32;
-    let mut count: usize = 0;
+    maze_part_1_with_budget(offsets, &Budget::default())
+        .expect("an unlimited budget is never exceeded")
+}
 
-    while next_index < length && next_index >= 0 {
-        index = next_index as usize;
-        next_index = index as i32 + offsets[index];
+fn maze_part_2(offsets: &mut [i32]) -> usize {
+    maze_part_2_with_budget(offsets, &Budget::default())
+        .expect("an unlimited budget is never exceeded")
+}
 
-        offsets[index] += 1;
-        count += 1;
-    }
+fn maze_part_1_with_budget(offsets: &mut [i32], budget: &Budget) -> Result<usize, Halted> {
+    escape(offsets, budget, |_| 1)
+}
 
-    count
+fn maze_part_2_with_budget(offsets: &mut [i32], budget: &Budget) -> Result<usize, Halted> {
+    escape(offsets, budget, |offset| if offset >= 3 { -1 } else { 1 })
 }
 
-fn maze_part_2(offsets: &mut [i32]) -> usize {
+// Follows the jumps until they lead out of the maze, changing each offset
+// after it is used by the amount `change` gives for its old value.
+fn escape<F>(offsets: &mut [i32], budget: &Budget, change: F) -> Result<usize, Halted>
+where
+    F: Fn(i32) -> i32,
+{
     let mut index: usize;
     let mut next_index: i32 = 0;
     let length = offsets.len() as i32;
     let mut count: usize = 0;
+    let mut watchdog = Watchdog::new(budget);
 
     while next_index < length && next_index >= 0 {
+        watchdog.tick(&(next_index, &*offsets))?;
         index = next_index as usize;
         next_index = index as i32 + offsets[index];
 
-        offsets[index] += if offsets[index] >= 3 { -1 } else { 1 };
+        offsets[index] += change(offsets[index]);
         count += 1;
     }
 
-    count
+    Ok(count)
 }
 
 #[cfg(test)]
@@ -73,4 +83,19 @@ mod tests {
     fn part2_example_requires_10_steps() {
         assert_eq!(maze_part_2(&mut example_input()), 10);
     }
+
+    #[test]
+    fn budget_limits_steps() {
+        let budget = Budget {
+            max_steps: Some(7),
+            detect_loops: true,
+            ..Budget::default()
+        };
+
+        assert_eq!(maze_part_1_with_budget(&mut example_input(), &budget), Ok(5));
+        assert_eq!(
+            maze_part_2_with_budget(&mut example_input(), &budget),
+            Err(Halted::StepLimit)
+        );
+    }
 }
@@ -0,0 +1,618 @@
+use super::Bit;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Bound, Index,
+               Not, RangeBounds};
+
+const WORD_BITS: usize = 64;
+
+static ZERO: Bit = Bit::Zero;
+static ONE: Bit = Bit::One;
+
+fn words_for(len: usize) -> usize {
+    len.div_ceil(WORD_BITS)
+}
+
+// Bits are stored most significant first, so that bit 0 of a BitVec built
+// from bytes is the top bit of the first byte.
+fn mask(index: usize) -> u64 {
+    1 << (WORD_BITS - 1 - index % WORD_BITS)
+}
+
+// Mask selecting bits `start..end` of a single word, where `end` may be 64.
+fn range_mask(start: usize, end: usize) -> u64 {
+    let high = if start == 0 { !0 } else { !0 >> start };
+    let low = if end == WORD_BITS {
+        !0
+    } else {
+        !(!0 >> end)
+    };
+
+    high & low
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BitVec {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVec {
+    pub fn new() -> BitVec {
+        BitVec::default()
+    }
+
+    pub fn from_elem(len: usize, bit: Bit) -> BitVec {
+        let fill = match bit {
+            Bit::Zero => 0,
+            Bit::One => !0,
+        };
+
+        let mut bits = BitVec {
+            words: vec![fill; words_for(len)],
+            len,
+        };
+        bits.clear_unused_bits();
+        bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Bit> {
+        if index >= self.len {
+            return None;
+        }
+
+        if self.words[index / WORD_BITS] & mask(index) == 0 {
+            Some(Bit::Zero)
+        } else {
+            Some(Bit::One)
+        }
+    }
+
+    pub fn set(&mut self, index: usize, bit: Bit) {
+        assert!(
+            index < self.len,
+            "index {} out of range for BitVec of length {}",
+            index,
+            self.len
+        );
+
+        match bit {
+            Bit::Zero => self.words[index / WORD_BITS] &= !mask(index),
+            Bit::One => self.words[index / WORD_BITS] |= mask(index),
+        }
+    }
+
+    pub fn push(&mut self, bit: Bit) {
+        if self.len.is_multiple_of(WORD_BITS) {
+            self.words.push(0);
+        }
+
+        self.len += 1;
+        let index = self.len - 1;
+        self.set(index, bit);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            bits: self,
+            start: 0,
+            end: self.len,
+        }
+    }
+
+    pub fn as_slice(&self) -> BitSlice<'_> {
+        BitSlice {
+            bits: self,
+            start: 0,
+            len: self.len,
+        }
+    }
+
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> BitSlice<'_> {
+        self.as_slice().slice(range)
+    }
+
+    fn count_ones_in(&self, start: usize, end: usize) -> usize {
+        if start == end {
+            return 0;
+        }
+
+        let first_word = start / WORD_BITS;
+        let last_word = (end - 1) / WORD_BITS;
+
+        (first_word..last_word + 1)
+            .map(|word_index| {
+                let word_start = word_index * WORD_BITS;
+                let from = start.saturating_sub(word_start);
+                let to = if end - word_start < WORD_BITS {
+                    end - word_start
+                } else {
+                    WORD_BITS
+                };
+
+                (self.words[word_index] & range_mask(from, to)).count_ones() as usize
+            })
+            .sum()
+    }
+
+    fn clear_unused_bits(&mut self) {
+        let used = self.len % WORD_BITS;
+        if used != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= range_mask(0, used);
+            }
+        }
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>(&mut self, other: &BitVec, op: F) {
+        assert_eq!(
+            self.len, other.len,
+            "bitwise operations require BitVecs of equal length"
+        );
+
+        for (word, &other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word = op(*word, other_word);
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for BitVec {
+    fn from(bytes: &'a [u8]) -> BitVec {
+        let words = bytes
+            .chunks(WORD_BITS / 8)
+            .map(|chunk| {
+                let packed = chunk
+                    .iter()
+                    .fold(0u64, |word, &byte| (word << 8) | u64::from(byte));
+                packed << (WORD_BITS - 8 * chunk.len())
+            })
+            .collect();
+
+        BitVec {
+            words,
+            len: bytes.len() * 8,
+        }
+    }
+}
+
+impl FromIterator<Bit> for BitVec {
+    fn from_iter<I: IntoIterator<Item = Bit>>(iter: I) -> BitVec {
+        let mut bits = BitVec::new();
+        bits.extend(iter);
+        bits
+    }
+}
+
+impl Extend<Bit> for BitVec {
+    fn extend<I: IntoIterator<Item = Bit>>(&mut self, iter: I) {
+        for bit in iter {
+            self.push(bit);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a BitVec {
+    type Item = Bit;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Index<usize> for BitVec {
+    type Output = Bit;
+
+    fn index(&self, index: usize) -> &Bit {
+        match self.get(index) {
+            Some(Bit::Zero) => &ZERO,
+            Some(Bit::One) => &ONE,
+            None => panic!(
+                "index {} out of range for BitVec of length {}",
+                index, self.len
+            ),
+        }
+    }
+}
+
+impl fmt::Display for BitVec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+
+impl PartialEq<[Bit]> for BitVec {
+    fn eq(&self, other: &[Bit]) -> bool {
+        self.as_slice() == *other
+    }
+}
+
+impl PartialEq<Vec<Bit>> for BitVec {
+    fn eq(&self, other: &Vec<Bit>) -> bool {
+        self.as_slice() == *other.as_slice()
+    }
+}
+
+impl<'a> BitAndAssign<&'a BitVec> for BitVec {
+    fn bitand_assign(&mut self, other: &'a BitVec) {
+        self.combine(other, |a, b| a & b);
+    }
+}
+
+impl<'a> BitOrAssign<&'a BitVec> for BitVec {
+    fn bitor_assign(&mut self, other: &'a BitVec) {
+        self.combine(other, |a, b| a | b);
+    }
+}
+
+impl<'a> BitXorAssign<&'a BitVec> for BitVec {
+    fn bitxor_assign(&mut self, other: &'a BitVec) {
+        self.combine(other, |a, b| a ^ b);
+    }
+}
+
+impl BitAnd<&BitVec> for &BitVec {
+    type Output = BitVec;
+
+    fn bitand(self, other: &BitVec) -> BitVec {
+        let mut result = self.clone();
+        result &= other;
+        result
+    }
+}
+
+impl BitOr<&BitVec> for &BitVec {
+    type Output = BitVec;
+
+    fn bitor(self, other: &BitVec) -> BitVec {
+        let mut result = self.clone();
+        result |= other;
+        result
+    }
+}
+
+impl BitXor<&BitVec> for &BitVec {
+    type Output = BitVec;
+
+    fn bitxor(self, other: &BitVec) -> BitVec {
+        let mut result = self.clone();
+        result ^= other;
+        result
+    }
+}
+
+impl Not for &BitVec {
+    type Output = BitVec;
+
+    fn not(self) -> BitVec {
+        !self.clone()
+    }
+}
+
+impl Not for BitVec {
+    type Output = BitVec;
+
+    fn not(mut self) -> BitVec {
+        for word in &mut self.words {
+            *word = !*word;
+        }
+
+        self.clear_unused_bits();
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BitSlice<'a> {
+    bits: &'a BitVec,
+    start: usize,
+    len: usize,
+}
+
+impl<'a> BitSlice<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> BitSlice<'a> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+
+        assert!(
+            start <= end && end <= self.len,
+            "range {}..{} out of range for slice of length {}",
+            start,
+            end,
+            self.len
+        );
+
+        BitSlice {
+            bits: self.bits,
+            start: self.start + start,
+            len: end - start,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Bit> {
+        if index < self.len {
+            self.bits.get(self.start + index)
+        } else {
+            None
+        }
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones_in(self.start, self.start + self.len)
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    pub fn iter(&self) -> Iter<'a> {
+        Iter {
+            bits: self.bits,
+            start: self.start,
+            end: self.start + self.len,
+        }
+    }
+
+    pub fn to_bit_vec(&self) -> BitVec {
+        self.iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for BitSlice<'a> {
+    type Item = Bit;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> Index<usize> for BitSlice<'a> {
+    type Output = Bit;
+
+    fn index(&self, index: usize) -> &Bit {
+        match self.get(index) {
+            Some(Bit::Zero) => &ZERO,
+            Some(Bit::One) => &ONE,
+            None => panic!(
+                "index {} out of range for BitSlice of length {}",
+                index, self.len
+            ),
+        }
+    }
+}
+
+impl<'a> fmt::Display for BitSlice<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for bit in self.iter() {
+            match bit {
+                Bit::Zero => write!(f, "0")?,
+                Bit::One => write!(f, "1")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, 'b> PartialEq<BitSlice<'b>> for BitSlice<'a> {
+    fn eq(&self, other: &BitSlice<'b>) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<'a> PartialEq<[Bit]> for BitSlice<'a> {
+    fn eq(&self, other: &[Bit]) -> bool {
+        self.len == other.len() && self.iter().eq(other.iter().cloned())
+    }
+}
+
+impl<'a> PartialEq<Vec<Bit>> for BitSlice<'a> {
+    fn eq(&self, other: &Vec<Bit>) -> bool {
+        *self == *other.as_slice()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    bits: &'a BitVec,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Bit;
+
+    fn next(&mut self) -> Option<Bit> {
+        if self.start == self.end {
+            return None;
+        }
+
+        let bit = self.bits.get(self.start);
+        self.start += 1;
+        bit
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.start;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<Bit> {
+        if self.start == self.end {
+            return None;
+        }
+
+        self.end -= 1;
+        self.bits.get(self.end)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_bits(pattern: &str) -> BitVec {
+        pattern
+            .chars()
+            .map(|c| if c == '1' { Bit::One } else { Bit::Zero })
+            .collect()
+    }
+
+    #[test]
+    fn from_bytes_is_most_significant_bit_first() {
+        let bytes = vec![0b1000_0001u8, 0b0100_0000];
+        let bits = BitVec::from(bytes.as_slice());
+
+        assert_eq!(bits.len(), 16);
+        assert_eq!(bits.to_string(), "1000000101000000");
+    }
+
+    #[test]
+    fn from_bytes_spanning_several_words() {
+        let bytes: Vec<u8> = (0..20).collect();
+        let bits = BitVec::from(bytes.as_slice());
+
+        assert_eq!(bits.len(), 160);
+        for (index, &byte) in bytes.iter().enumerate() {
+            let expected = BitVec::from(&[byte][..]);
+            assert_eq!(bits.slice(index * 8..index * 8 + 8), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn get_and_set() {
+        let mut bits = BitVec::from_elem(70, Bit::Zero);
+        bits.set(0, Bit::One);
+        bits.set(65, Bit::One);
+
+        assert_eq!(bits.get(0), Some(Bit::One));
+        assert_eq!(bits.get(1), Some(Bit::Zero));
+        assert_eq!(bits.get(65), Some(Bit::One));
+        assert_eq!(bits.get(70), None);
+
+        bits.set(0, Bit::Zero);
+        assert_eq!(bits[0], Bit::Zero);
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_out_of_range_panics() {
+        let mut bits = BitVec::from_elem(8, Bit::Zero);
+        bits.set(8, Bit::One);
+    }
+
+    #[test]
+    fn count_ones_and_zeros() {
+        let bits = BitVec::from_elem(130, Bit::One);
+        assert_eq!(bits.count_ones(), 130);
+        assert_eq!(bits.count_zeros(), 0);
+
+        let bits = parse_bits("0110100");
+        assert_eq!(bits.count_ones(), 3);
+        assert_eq!(bits.count_zeros(), 4);
+    }
+
+    #[test]
+    fn slice_count_ones_across_words() {
+        let bits = BitVec::from_elem(200, Bit::One);
+        assert_eq!(bits.slice(3..150).count_ones(), 147);
+        assert_eq!(bits.slice(64..128).count_ones(), 64);
+        assert_eq!(bits.slice(10..10).count_ones(), 0);
+        assert_eq!(bits.slice(..).count_ones(), 200);
+    }
+
+    #[test]
+    fn iterate_forwards_and_backwards() {
+        let bits = parse_bits("1101");
+        let forwards: Vec<Bit> = bits.iter().collect();
+        let backwards: Vec<Bit> = bits.iter().rev().collect();
+
+        assert_eq!(forwards, vec![Bit::One, Bit::One, Bit::Zero, Bit::One]);
+        assert_eq!(backwards, vec![Bit::One, Bit::Zero, Bit::One, Bit::One]);
+        assert_eq!(bits.iter().len(), 4);
+    }
+
+    #[test]
+    fn bitwise_operations() {
+        let a = parse_bits("1100");
+        let b = parse_bits("1010");
+
+        assert_eq!((&a & &b).to_string(), "1000");
+        assert_eq!((&a | &b).to_string(), "1110");
+        assert_eq!((&a ^ &b).to_string(), "0110");
+        assert_eq!((!&a).to_string(), "0011");
+    }
+
+    #[test]
+    fn not_leaves_unused_bits_clear() {
+        let bits = !BitVec::from_elem(3, Bit::Zero);
+        assert_eq!(bits.count_ones(), 3);
+        assert_eq!(bits, BitVec::from_elem(3, Bit::One));
+    }
+
+    #[test]
+    #[should_panic]
+    fn bitwise_operation_on_different_lengths_panics() {
+        let _ = &parse_bits("10") & &parse_bits("101");
+    }
+
+    #[test]
+    fn slices_compare_by_content() {
+        let a = parse_bits("0011");
+        let b = parse_bits("1100");
+
+        assert_eq!(a.slice(2..), b.slice(..2));
+        assert!(a.slice(..2) != b.slice(..2));
+        assert_eq!(a.slice(1..3), vec![Bit::Zero, Bit::One]);
+        assert_eq!(a.slice(1..=2).to_bit_vec(), parse_bits("01"));
+    }
+
+    #[test]
+    fn slice_of_slice() {
+        let bits = parse_bits("0011010");
+        let inner = bits.slice(2..6).slice(1..3);
+        assert_eq!(inner.to_string(), "10");
+        assert_eq!(inner.count_ones(), 1);
+    }
+}
@@ -0,0 +1,66 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    UnexpectedEof,
+    TooManyBits,
+    Io,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    io_error: Option<io::Error>,
+}
+
+impl Error {
+    pub fn unexpected_eof() -> Error {
+        Error {
+            kind: ErrorKind::UnexpectedEof,
+            io_error: None,
+        }
+    }
+
+    pub fn too_many_bits() -> Error {
+        Error {
+            kind: ErrorKind::TooManyBits,
+            io_error: None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error {
+            kind: ErrorKind::Io,
+            io_error: Some(error),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::UnexpectedEof => write!(f, "Stream ended before all bits were read"),
+            ErrorKind::TooManyBits => write!(f, "At most 64 bits can be transferred at once"),
+            ErrorKind::Io => match self.io_error {
+                Some(ref e) => write!(f, "I/O error: {}", e),
+                None => write!(f, "I/O error"),
+            },
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "Bit stream error"
+    }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.io_error
+            .as_ref()
+            .map(|e| e as &(dyn error::Error + 'static))
+    }
+}
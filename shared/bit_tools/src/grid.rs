@@ -0,0 +1,323 @@
+use super::{Bit, BitSlice, BitVec};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+// A rectangular grid of bits stored row by row in a single BitVec.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    bits: BitVec,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> BitGrid {
+        BitGrid {
+            width,
+            height,
+            bits: BitVec::from_elem(width * height, Bit::Zero),
+        }
+    }
+
+    // Panics if the rows are not all the same length.
+    pub fn from_rows<I: IntoIterator<Item = BitVec>>(rows: I) -> BitGrid {
+        let mut bits = BitVec::new();
+        let mut width: Option<usize> = None;
+        let mut height = 0;
+
+        for row in rows {
+            let row_width = *width.get_or_insert(row.len());
+            assert_eq!(row.len(), row_width, "all rows of a grid must be the same length");
+
+            bits.extend(&row);
+            height += 1;
+        }
+
+        BitGrid {
+            width: width.unwrap_or(0),
+            height,
+            bits,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<Bit> {
+        if row < self.height && col < self.width {
+            self.bits.get(row * self.width + col)
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, bit: Bit) {
+        assert!(
+            row < self.height && col < self.width,
+            "({}, {}) is outside a {}x{} grid",
+            row,
+            col,
+            self.height,
+            self.width
+        );
+
+        self.bits.set(row * self.width + col, bit);
+    }
+
+    pub fn row(&self, row: usize) -> BitSlice<'_> {
+        self.bits.slice(row * self.width..(row + 1) * self.width)
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    pub fn neighbours(
+        &self,
+        row: usize,
+        col: usize,
+        connectivity: Connectivity,
+    ) -> Vec<(usize, usize)> {
+        self.neighbour_cells(row, col, connectivity).collect()
+    }
+
+    fn neighbour_cells(
+        &self,
+        row: usize,
+        col: usize,
+        connectivity: Connectivity,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let offsets: &'static [(isize, isize)] = match connectivity {
+            Connectivity::Four => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        };
+
+        offsets.iter().filter_map(move |&(d_row, d_col)| {
+            let r = row.checked_add_signed(d_row)?;
+            let c = col.checked_add_signed(d_col)?;
+            if r < self.height && c < self.width {
+                Some((r, c))
+            } else {
+                None
+            }
+        })
+    }
+
+    // Returns every set cell connected to `start`, including `start` itself.
+    // The result is empty if `start` is not set.
+    pub fn flood_fill(&self, start: (usize, usize), connectivity: Connectivity) -> Vec<(usize, usize)> {
+        let mut visited = BitVec::from_elem(self.bits.len(), Bit::Zero);
+        let mut cells = Vec::new();
+        self.fill_from(start, connectivity, &mut visited, &mut Vec::new(), &mut cells);
+        cells
+    }
+
+    pub fn label_components(&self, connectivity: Connectivity) -> Components {
+        let mut visited = BitVec::from_elem(self.bits.len(), Bit::Zero);
+        let mut labels: Vec<Option<usize>> = vec![None; self.bits.len()];
+        let mut sizes: Vec<usize> = Vec::new();
+
+        // Shared by every fill, so that scanning the grid does not allocate
+        // once the buffers have grown to fit the largest region.
+        let mut pending = Vec::new();
+        let mut cells = Vec::new();
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                cells.clear();
+                self.fill_from((row, col), connectivity, &mut visited, &mut pending, &mut cells);
+                if cells.is_empty() {
+                    continue;
+                }
+
+                let label = sizes.len();
+                for &(r, c) in &cells {
+                    labels[r * self.width + c] = Some(label);
+                }
+                sizes.push(cells.len());
+            }
+        }
+
+        Components {
+            width: self.width,
+            labels,
+            sizes,
+        }
+    }
+
+    // Adds the cells reached from `start` to `cells`, using `pending` as the
+    // work stack. Iterative, so that large regions cannot overflow the stack.
+    fn fill_from(
+        &self,
+        start: (usize, usize),
+        connectivity: Connectivity,
+        visited: &mut BitVec,
+        pending: &mut Vec<(usize, usize)>,
+        cells: &mut Vec<(usize, usize)>,
+    ) {
+        pending.push(start);
+
+        while let Some((row, col)) = pending.pop() {
+            let index = row * self.width + col;
+            if self.get(row, col) != Some(Bit::One) || visited[index] == Bit::One {
+                continue;
+            }
+
+            visited.set(index, Bit::One);
+            cells.push((row, col));
+            pending.extend(self.neighbour_cells(row, col, connectivity));
+        }
+    }
+}
+
+// Connected regions of set cells, labelled from zero in the order in which
+// they are first reached scanning the grid row by row.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Components {
+    width: usize,
+    labels: Vec<Option<usize>>,
+    sizes: Vec<usize>,
+}
+
+impl Components {
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn label(&self, row: usize, col: usize) -> Option<usize> {
+        if col < self.width {
+            self.labels.get(row * self.width + col).cloned().unwrap_or(None)
+        } else {
+            None
+        }
+    }
+
+    // One entry per cell, row by row.
+    pub fn labels(&self) -> &[Option<usize>] {
+        &self.labels
+    }
+
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> BitGrid {
+        BitGrid::from_rows(rows.iter().map(|row| {
+            row.chars()
+                .map(|c| if c == '#' { Bit::One } else { Bit::Zero })
+                .collect()
+        }))
+    }
+
+    #[test]
+    fn from_rows_dimensions() {
+        let grid = grid(&["#..", "...", ".##", ".##"]);
+        assert_eq!(grid.width(), 3);
+        assert_eq!(grid.height(), 4);
+        assert_eq!(grid.count_ones(), 5);
+        assert_eq!(grid.get(2, 1), Some(Bit::One));
+        assert_eq!(grid.get(4, 0), None);
+        assert_eq!(grid.row(2).to_string(), "011");
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_rows_of_different_lengths_panics() {
+        grid(&["#..", "##"]);
+    }
+
+    #[test]
+    fn set_cells() {
+        let mut grid = BitGrid::new(4, 2);
+        grid.set(1, 3, Bit::One);
+        assert_eq!(grid.get(1, 3), Some(Bit::One));
+        assert_eq!(grid.count_ones(), 1);
+    }
+
+    #[test]
+    fn neighbours_at_corner() {
+        let grid = BitGrid::new(3, 3);
+        assert_eq!(grid.neighbours(0, 0, Connectivity::Four), vec![(0, 1), (1, 0)]);
+        assert_eq!(
+            grid.neighbours(0, 0, Connectivity::Eight),
+            vec![(0, 1), (1, 0), (1, 1)]
+        );
+        assert_eq!(grid.neighbours(1, 1, Connectivity::Eight).len(), 8);
+    }
+
+    #[test]
+    fn flood_fill_region() {
+        let grid = grid(&["##.", "..#", ".##"]);
+
+        let mut cells = grid.flood_fill((2, 2), Connectivity::Four);
+        cells.sort();
+        assert_eq!(cells, vec![(1, 2), (2, 1), (2, 2)]);
+
+        assert!(grid.flood_fill((1, 0), Connectivity::Four).is_empty());
+        assert_eq!(grid.flood_fill((0, 0), Connectivity::Eight).len(), 5);
+    }
+
+    #[test]
+    fn count_two_regions() {
+        let grid = grid(&["#..", "...", ".##", ".##"]);
+        let components = grid.label_components(Connectivity::Four);
+
+        assert_eq!(components.count(), 2);
+        assert_eq!(components.sizes(), &[1, 4]);
+        assert_eq!(components.label(0, 0), Some(0));
+        assert_eq!(components.label(3, 2), Some(1));
+        assert_eq!(components.label(1, 1), None);
+        assert_eq!(components.label(0, 3), None);
+    }
+
+    #[test]
+    fn diagonal_connectivity_joins_regions() {
+        let grid = grid(&["#.#", ".#.", "#.#"]);
+
+        assert_eq!(grid.label_components(Connectivity::Four).count(), 5);
+
+        let components = grid.label_components(Connectivity::Eight);
+        assert_eq!(components.count(), 1);
+        assert_eq!(components.sizes(), &[5]);
+    }
+
+    #[test]
+    fn large_region_does_not_overflow_stack() {
+        let size = 1000;
+        let rows = (0..size).map(|_| BitVec::from_elem(size, Bit::One));
+        let grid = BitGrid::from_rows(rows);
+
+        let components = grid.label_components(Connectivity::Four);
+        assert_eq!(components.sizes(), &[size * size]);
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid = BitGrid::from_rows(Vec::new());
+        assert_eq!(grid.width(), 0);
+        assert_eq!(grid.label_components(Connectivity::Four).count(), 0);
+    }
+}
@@ -1,37 +1,92 @@
-#[derive(Debug, Eq, PartialEq)]
+mod bit_vec;
+pub mod errors;
+mod grid;
+mod stream;
+
+pub use bit_vec::{BitSlice, BitVec, Iter};
+pub use grid::{BitGrid, Components, Connectivity};
+pub use stream::{BitReader, BitWriter};
+use std::mem;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
 pub enum Bit {
     Zero,
     One,
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst,
+    LsbFirst,
+}
+
 pub trait AsBits {
-    fn as_bits(&self) -> Vec<Bit>;
+    fn as_bits(&self) -> BitVec {
+        self.as_bits_ordered(BitOrder::MsbFirst)
+    }
+
+    fn as_bits_ordered(&self, order: BitOrder) -> BitVec;
 }
 
-impl AsBits for u8 {
-    fn as_bits(&self) -> Vec<Bit> {
-        let mut bits: Vec<Bit> = Vec::with_capacity(8);
+pub trait FromBits: Sized {
+    // Returns None if there are more bits than fit in the type. Missing
+    // bits are treated as leading zeros.
+    fn from_bits<I: IntoIterator<Item = Bit>>(bits: I, order: BitOrder) -> Option<Self>;
+}
 
-        for n in (0..8).rev() {
-            let bit = if self & (1 << n) == 0 {
-                Bit::Zero
-            } else {
-                Bit::One
-            };
+macro_rules! impl_bits_for_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl AsBits for $t {
+                fn as_bits_ordered(&self, order: BitOrder) -> BitVec {
+                    let width = mem::size_of::<$t>() * 8;
+                    let bit_at = |n: usize| {
+                        if (*self >> n) & 1 == 0 {
+                            Bit::Zero
+                        } else {
+                            Bit::One
+                        }
+                    };
 
-            bits.push(bit);
-        }
+                    match order {
+                        BitOrder::MsbFirst => (0..width).rev().map(bit_at).collect(),
+                        BitOrder::LsbFirst => (0..width).map(bit_at).collect(),
+                    }
+                }
+            }
 
-        bits
-    }
+            impl FromBits for $t {
+                fn from_bits<I: IntoIterator<Item = Bit>>(bits: I, order: BitOrder) -> Option<$t> {
+                    let width = mem::size_of::<$t>() * 8;
+                    let mut value: $t = 0;
+
+                    for (n, bit) in bits.into_iter().enumerate() {
+                        if n == width {
+                            return None;
+                        }
+
+                        let bit_value: $t = if bit == Bit::One { 1 } else { 0 };
+                        match order {
+                            BitOrder::MsbFirst => value = (value << 1) | bit_value,
+                            BitOrder::LsbFirst => value |= bit_value << n,
+                        }
+                    }
+
+                    Some(value)
+                }
+            }
+        )*
+    };
 }
 
-impl<'a> AsBits for &'a[u8] {
-    fn as_bits(&self) -> Vec<Bit> {
-        let mut bits: Vec<Bit> = Vec::with_capacity(self.len() * 8);
+impl_bits_for_unsigned!(u8, u16, u32, u64, u128, usize);
 
-        for &byte in self.iter() {
-            bits.append(&mut byte.as_bits());
+impl<T: AsBits> AsBits for [T] {
+    fn as_bits_ordered(&self, order: BitOrder) -> BitVec {
+        let mut bits = BitVec::new();
+        for item in self {
+            bits.extend(&item.as_bits_ordered(order));
         }
 
         bits
@@ -47,8 +102,8 @@ mod tests {
         let bits = 0u8.as_bits();
         assert_eq!(bits.len(), 8);
 
-        for n in 0..8 {
-            assert_eq!(bits[n], Bit::Zero);
+        for bit in bits.iter() {
+            assert_eq!(bit, Bit::Zero);
         }
     }
 
@@ -132,4 +187,79 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn u8_lsb_first() {
+        let bits = 6u8.as_bits_ordered(BitOrder::LsbFirst);
+        assert_eq!(bits.to_string(), "01100000");
+    }
+
+    #[test]
+    fn u16_as_bits() {
+        let bits = 0xA001u16.as_bits();
+        assert_eq!(bits.len(), 16);
+        assert_eq!(bits.to_string(), "1010000000000001");
+    }
+
+    #[test]
+    fn wider_integers_as_bits() {
+        assert_eq!(1u32.as_bits().len(), 32);
+        assert_eq!(u64::MAX.as_bits().count_ones(), 64);
+        assert_eq!((1u128 << 100).as_bits().count_ones(), 1);
+        assert_eq!(0usize.as_bits().len(), mem::size_of::<usize>() * 8);
+    }
+
+    #[test]
+    fn byte_slice_lsb_first() {
+        let input_bytes = vec![1u8, 128u8];
+        let bits = input_bytes.as_slice().as_bits_ordered(BitOrder::LsbFirst);
+        assert_eq!(bits.to_string(), "1000000000000001");
+    }
+
+    #[test]
+    fn u16_slice_as_bits() {
+        let bits = [1u16, 0x8000].as_bits();
+        assert_eq!(bits.len(), 32);
+        assert_eq!(bits.to_string(), "00000000000000011000000000000000");
+    }
+
+    #[test]
+    fn from_bits_round_trip() {
+        for &order in &[BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            assert_eq!(u8::from_bits(&200u8.as_bits_ordered(order), order), Some(200));
+            assert_eq!(
+                u16::from_bits(&0xBEEFu16.as_bits_ordered(order), order),
+                Some(0xBEEF)
+            );
+            assert_eq!(
+                u64::from_bits(&0x0123_4567_89AB_CDEFu64.as_bits_ordered(order), order),
+                Some(0x0123_4567_89AB_CDEF)
+            );
+            assert_eq!(
+                u128::from_bits(&u128::MAX.as_bits_ordered(order), order),
+                Some(u128::MAX)
+            );
+        }
+    }
+
+    #[test]
+    fn from_fewer_bits_than_width() {
+        let bits = vec![Bit::One, Bit::Zero];
+        assert_eq!(u32::from_bits(bits.clone(), BitOrder::MsbFirst), Some(2));
+        assert_eq!(u32::from_bits(bits, BitOrder::LsbFirst), Some(1));
+    }
+
+    #[test]
+    fn from_too_many_bits() {
+        let bits = 0x1FFu16.as_bits();
+        assert_eq!(u8::from_bits(&bits, BitOrder::MsbFirst), None);
+        assert_eq!(u8::from_bits(bits.slice(8..), BitOrder::MsbFirst), Some(0xFF));
+    }
+
+    #[test]
+    fn lower_16_bits_by_conversion() {
+        let bits = 245_556_042usize.as_bits();
+        let low = u16::from_bits(bits.slice(bits.len() - 16..), BitOrder::MsbFirst);
+        assert_eq!(low, Some((245_556_042 & 0xFFFF) as u16));
+    }
 }
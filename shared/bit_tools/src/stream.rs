@@ -0,0 +1,327 @@
+use super::Bit;
+use errors::Error;
+use std::io::{ErrorKind, Read, Write};
+
+// Both the reader and the writer treat bytes as most significant bit first,
+// and multi-bit values are transferred most significant bit first.
+const MAX_BITS: u32 = 64;
+
+#[derive(Debug)]
+pub struct BitReader<R: Read> {
+    inner: R,
+    current: u8,
+    bits_remaining: u32,
+    position: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    pub fn new(inner: R) -> BitReader<R> {
+        BitReader {
+            inner,
+            current: 0,
+            bits_remaining: 0,
+            position: 0,
+        }
+    }
+
+    // The number of bits consumed so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn is_aligned(&self) -> bool {
+        self.bits_remaining == 0
+    }
+
+    pub fn read_bit(&mut self) -> Result<Bit, Error> {
+        if self.bits_remaining == 0 {
+            self.current = self.next_byte()?;
+            self.bits_remaining = 8;
+        }
+
+        self.bits_remaining -= 1;
+        self.position += 1;
+
+        if self.current & (1 << self.bits_remaining) == 0 {
+            Ok(Bit::Zero)
+        } else {
+            Ok(Bit::One)
+        }
+    }
+
+    pub fn read_bits(&mut self, count: u32) -> Result<u64, Error> {
+        if count > MAX_BITS {
+            return Err(Error::too_many_bits());
+        }
+
+        let mut value: u64 = 0;
+        for _ in 0..count {
+            let bit = match self.read_bit()? {
+                Bit::Zero => 0,
+                Bit::One => 1,
+            };
+            value = (value << 1) | bit;
+        }
+
+        Ok(value)
+    }
+
+    // Discards any bits left over from a partially read byte.
+    pub fn align(&mut self) {
+        self.position += u64::from(self.bits_remaining);
+        self.bits_remaining = 0;
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn next_byte(&mut self) -> Result<u8, Error> {
+        let mut buffer = [0u8; 1];
+        loop {
+            match self.inner.read(&mut buffer) {
+                Ok(0) => return Err(Error::unexpected_eof()),
+                Ok(_) => return Ok(buffer[0]),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::from(e)),
+            }
+        }
+    }
+}
+
+// Bits are passed on to the underlying writer a whole byte at a time. A
+// partly written last byte only goes out through `align` or `into_inner`;
+// if the writer is just dropped, those bits are lost.
+#[derive(Debug)]
+pub struct BitWriter<W: Write> {
+    inner: W,
+    current: u8,
+    bits_used: u32,
+    position: u64,
+}
+
+impl<W: Write> BitWriter<W> {
+    pub fn new(inner: W) -> BitWriter<W> {
+        BitWriter {
+            inner,
+            current: 0,
+            bits_used: 0,
+            position: 0,
+        }
+    }
+
+    // The number of bits written so far, including any not yet passed on to
+    // the underlying writer.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn is_aligned(&self) -> bool {
+        self.bits_used == 0
+    }
+
+    // If the underlying writer fails, the bit is not written, so the same
+    // bit can be written again.
+    pub fn write_bit(&mut self, bit: Bit) -> Result<(), Error> {
+        let mut current = self.current;
+        if bit == Bit::One {
+            current |= 1 << (7 - self.bits_used);
+        }
+
+        if self.bits_used == 7 {
+            self.inner.write_all(&[current])?;
+            self.current = 0;
+            self.bits_used = 0;
+        } else {
+            self.current = current;
+            self.bits_used += 1;
+        }
+
+        self.position += 1;
+        Ok(())
+    }
+
+    // Writes the lowest `count` bits of `value`.
+    pub fn write_bits(&mut self, value: u64, count: u32) -> Result<(), Error> {
+        if count > MAX_BITS {
+            return Err(Error::too_many_bits());
+        }
+
+        for n in (0..count).rev() {
+            let bit = if (value >> n) & 1 == 0 {
+                Bit::Zero
+            } else {
+                Bit::One
+            };
+            self.write_bit(bit)?;
+        }
+
+        Ok(())
+    }
+
+    // Pads a partially written byte with zeros so that it can be written out.
+    pub fn align(&mut self) -> Result<(), Error> {
+        while !self.is_aligned() {
+            self.write_bit(Bit::Zero)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    // Pads and writes out any partial byte before returning the writer.
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        self.align()?;
+        self.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ErrorKind;
+    use std::io;
+
+    // Fails the first `failures` writes, then collects everything written.
+    struct Unreliable {
+        failures: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for Unreliable {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(io::Error::other("disk full"));
+            }
+
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_single_bits() {
+        let mut reader = BitReader::new(&[0b1010_0000u8][..]);
+        assert_eq!(reader.read_bit().unwrap(), Bit::One);
+        assert_eq!(reader.read_bit().unwrap(), Bit::Zero);
+        assert_eq!(reader.read_bit().unwrap(), Bit::One);
+        assert_eq!(reader.position(), 3);
+    }
+
+    #[test]
+    fn read_fields_across_byte_boundaries() {
+        let mut reader = BitReader::new(&[0b1101_0110u8, 0b0111_1111, 0b1000_0000][..]);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b110);
+        assert_eq!(reader.read_bits(16).unwrap(), 0b1011_0011_1111_1100);
+        assert_eq!(reader.position(), 19);
+    }
+
+    #[test]
+    fn read_zero_bits() {
+        let mut reader = BitReader::new(&[][..]);
+        assert_eq!(reader.read_bits(0).unwrap(), 0);
+        assert_eq!(reader.position(), 0);
+    }
+
+    #[test]
+    fn premature_end_of_stream() {
+        let mut reader = BitReader::new(&[0xFFu8][..]);
+        let error = reader.read_bits(9).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn too_many_bits() {
+        let mut reader = BitReader::new(&[0u8; 9][..]);
+        assert_eq!(reader.read_bits(65).unwrap_err().kind, ErrorKind::TooManyBits);
+
+        let mut writer = BitWriter::new(Vec::new());
+        assert_eq!(
+            writer.write_bits(0, 65).unwrap_err().kind,
+            ErrorKind::TooManyBits
+        );
+    }
+
+    #[test]
+    fn read_64_bits() {
+        let mut reader = BitReader::new(&[0xFFu8; 8][..]);
+        assert_eq!(reader.read_bits(64).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn align_reader_skips_rest_of_byte() {
+        let mut reader = BitReader::new(&[0b1000_0000u8, 0b0100_0000][..]);
+        assert_eq!(reader.read_bit().unwrap(), Bit::One);
+        reader.align();
+        assert_eq!(reader.position(), 8);
+        assert_eq!(reader.read_bits(2).unwrap(), 0b01);
+    }
+
+    #[test]
+    fn write_fields_across_byte_boundaries() {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bits(0b110, 3).unwrap();
+        writer.write_bits(0xFFFF, 16).unwrap();
+        assert_eq!(writer.position(), 19);
+
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes, vec![0b1101_1111, 0b1111_1111, 0b1110_0000]);
+    }
+
+    #[test]
+    fn writer_only_passes_on_complete_bytes() {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bits(0b1011, 4).unwrap();
+        assert!(!writer.is_aligned());
+
+        writer.align().unwrap();
+        assert!(writer.is_aligned());
+        assert_eq!(writer.position(), 8);
+        assert_eq!(writer.into_inner().unwrap(), vec![0b1011_0000]);
+    }
+
+    #[test]
+    fn round_trip_16_bit_fields() {
+        let values = [245_556_042u64 & 0xFFFF, 1_431_495_498 & 0xFFFF, 0, 0xFFFF];
+
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bit(Bit::One).unwrap();
+        for &value in &values {
+            writer.write_bits(value, 16).unwrap();
+        }
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes.len(), 9);
+
+        let mut reader = BitReader::new(bytes.as_slice());
+        assert_eq!(reader.read_bit().unwrap(), Bit::One);
+        for &value in &values {
+            assert_eq!(reader.read_bits(16).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn failed_write_can_be_retried() {
+        let mut writer = BitWriter::new(Unreliable {
+            failures: 1,
+            written: Vec::new(),
+        });
+        writer.write_bits(0b101_0101, 7).unwrap();
+
+        assert_eq!(writer.write_bit(Bit::One).unwrap_err().kind, ErrorKind::Io);
+        assert_eq!(writer.position(), 7);
+
+        writer.write_bit(Bit::One).unwrap();
+        writer.write_bits(0b11, 2).unwrap();
+        assert_eq!(writer.position(), 10);
+        assert_eq!(writer.into_inner().unwrap().written, vec![0b1010_1011, 0b1100_0000]);
+    }
+}
@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate criterion;
+extern crate knot;
+
+use criterion::{black_box, Criterion};
+use knot::KnotHash;
+
+// The original implementation, which collects every reversed segment into a
+// freshly allocated vector, kept here as a baseline for comparison.
+mod allocating {
+    fn reverse_segment(numbers: &mut [u8], position: usize, segment_length: u8) {
+        let numbers_length = numbers.len();
+        let mut reversed: Vec<u8> = numbers
+            .iter()
+            .cycle()
+            .skip(position)
+            .take(segment_length as usize)
+            .cloned()
+            .collect();
+
+        reversed.reverse();
+
+        for (index, &n) in reversed.iter().enumerate() {
+            numbers[(position + index) % numbers_length] = n;
+        }
+    }
+
+    pub fn knot_hash(input: &str) -> Vec<u8> {
+        let mut lengths: Vec<u8> = input.to_string().into_bytes();
+        lengths.extend_from_slice(&[17, 31, 73, 47, 23]);
+
+        let mut numbers: Vec<u8> = (0..256).map(|n| n as u8).collect();
+        let mut position: usize = 0;
+        let mut skip: usize = 0;
+
+        for _ in 0..64 {
+            for &length in &lengths {
+                reverse_segment(&mut numbers, position, length);
+                position = (position + length as usize + skip) % numbers.len();
+                skip += 1;
+            }
+        }
+
+        numbers
+            .chunks(16)
+            .map(|chunk| chunk.iter().fold(0, |acc, x| acc ^ x))
+            .collect()
+    }
+}
+
+fn compare_implementations(c: &mut Criterion) {
+    let input = "flqrgnkx-127";
+    assert_eq!(
+        KnotHash::new(input).as_bytes(),
+        allocating::knot_hash(input).as_slice()
+    );
+
+    let mut group = c.benchmark_group("knot_hash");
+    group.bench_function("in_place", |b| b.iter(|| KnotHash::new(black_box(input))));
+    group.bench_function("allocating", |b| {
+        b.iter(|| allocating::knot_hash(black_box(input)))
+    });
+    group.finish();
+}
+
+fn disk_grid(c: &mut Criterion) {
+    c.bench_function("disk_grid_128_rows", |b| {
+        b.iter(|| {
+            (0..128)
+                .map(|n| KnotHash::new(&format!("flqrgnkx-{}", n)))
+                .collect::<Vec<KnotHash>>()
+        })
+    });
+}
+
+criterion_group!(benches, compare_implementations, disk_grid);
+criterion_main!(benches);
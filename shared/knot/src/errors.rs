@@ -0,0 +1,44 @@
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    InvalidLength,
+    InvalidDigit,
+}
+
+#[derive(Debug)]
+pub struct ParseKnotHashError {
+    pub kind: ErrorKind,
+}
+
+impl ParseKnotHashError {
+    pub fn invalid_length() -> ParseKnotHashError {
+        ParseKnotHashError {
+            kind: ErrorKind::InvalidLength,
+        }
+    }
+
+    pub fn invalid_digit() -> ParseKnotHashError {
+        ParseKnotHashError {
+            kind: ErrorKind::InvalidDigit,
+        }
+    }
+}
+
+impl fmt::Display for ParseKnotHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::InvalidLength => {
+                write!(f, "A knot hash must have two hex digits for every byte")
+            }
+            ErrorKind::InvalidDigit => write!(f, "Invalid hexadecimal digit"),
+        }
+    }
+}
+
+impl error::Error for ParseKnotHashError {
+    fn description(&self) -> &str {
+        "Invalid knot hash"
+    }
+}
@@ -1,27 +1,53 @@
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+pub mod errors;
+#[cfg(feature = "serde")]
+mod serialization;
+
+pub use errors::ParseKnotHashError;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::cell::Cell;
 use std::fmt;
+use std::hash::Hasher;
+use std::io;
+use std::str::FromStr;
+
+const DEFAULT_RING_SIZE: usize = 256;
+const DEFAULT_ROUNDS: usize = 64;
+const DEFAULT_SUFFIX: [u8; 5] = [17, 31, 73, 47, 23];
+const DEFAULT_BLOCK_SIZE: usize = 16;
 
-fn get_lengths(input: &str) -> Vec<u8> {
-    let mut lengths: Vec<u8> = input.to_string().into_bytes();
-    let mut suffix: Vec<u8> = vec![17, 31, 73, 47, 23];
-    lengths.append(&mut suffix);
+fn get_lengths(input: &[u8], suffix: &[u8]) -> Vec<u8> {
+    let mut lengths: Vec<u8> = input.to_vec();
+    lengths.extend_from_slice(suffix);
 
     lengths
 }
 
 fn reverse_segment(numbers: &mut [u8], position: usize, segment_length: u8) {
     let numbers_length = numbers.len();
-    let mut reversed: Vec<u8> = numbers
-        .iter()
-        .cycle()
-        .skip(position)
-        .take(segment_length as usize)
-        .cloned()
-        .collect();
+    let segment_length = segment_length as usize;
+
+    if segment_length < 2 {
+        return;
+    }
+
+    let mut start = position % numbers_length;
+    let mut end = (position + segment_length - 1) % numbers_length;
 
-    reversed.reverse();
+    for _ in 0..segment_length / 2 {
+        numbers.swap(start, end);
 
-    for (index, &n) in reversed.iter().enumerate() {
-        numbers[(position + index) % numbers_length] = n;
+        start += 1;
+        if start == numbers_length {
+            start = 0;
+        }
+
+        end = if end == 0 { numbers_length - 1 } else { end - 1 };
     }
 }
 
@@ -29,49 +55,216 @@ fn knot(numbers: &mut [u8], lengths: &[u8], position: &mut usize, skip: &mut usi
     let numbers_length = numbers.len();
 
     for &length in lengths {
+        // Lengths larger than the list are invalid and are ignored.
+        if length as usize > numbers_length {
+            continue;
+        }
+
         reverse_segment(numbers, *position, length);
         *position = (*position + length as usize + *skip) % numbers_length;
         *skip += 1;
     }
 }
 
-fn sparse_hash(input_numbers: &[u8], lengths: &[u8]) -> Vec<u8> {
+// The ring never holds more than 256 elements, so the whole state lives on the
+// stack and only the first `ring_size` entries are used.
+fn sparse_hash(ring_size: usize, lengths: &[u8], rounds: usize) -> [u8; 256] {
     let mut position: usize = 0;
     let mut skip: usize = 0;
-    let mut numbers = input_numbers.to_vec();
+    let mut numbers = [0u8; 256];
+
+    for (index, n) in numbers.iter_mut().enumerate() {
+        *n = index as u8;
+    }
 
-    for _ in 0..64 {
-        knot(&mut numbers, lengths, &mut position, &mut skip);
+    for _ in 0..rounds {
+        knot(&mut numbers[..ring_size], lengths, &mut position, &mut skip);
     }
 
     numbers
 }
 
-fn dense_hash(input: &[u8]) -> Vec<u8> {
-    input.chunks(16usize).map(|chunk| xor_all(chunk)).collect()
+fn dense_hash(input: &[u8], block_size: usize) -> Vec<u8> {
+    input.chunks(block_size).map(xor_all).collect()
 }
 
 fn xor_all(chunk: &[u8]) -> u8 {
     chunk.iter().skip(1).fold(chunk[0], |acc, x| acc ^ x)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct KnotHashConfig {
+    ring_size: usize,
+    rounds: usize,
+    suffix: Vec<u8>,
+    block_size: usize,
+}
+
+impl Default for KnotHashConfig {
+    fn default() -> KnotHashConfig {
+        KnotHashConfig {
+            ring_size: DEFAULT_RING_SIZE,
+            rounds: DEFAULT_ROUNDS,
+            suffix: DEFAULT_SUFFIX.to_vec(),
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
+    }
+}
+
+impl KnotHashConfig {
+    pub fn new() -> KnotHashConfig {
+        KnotHashConfig::default()
+    }
+
+    pub fn ring_size(mut self, ring_size: usize) -> KnotHashConfig {
+        assert!(
+            ring_size > 0 && ring_size <= 256,
+            "ring size must be between 1 and 256"
+        );
+        self.ring_size = ring_size;
+        self
+    }
+
+    pub fn rounds(mut self, rounds: usize) -> KnotHashConfig {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn suffix(mut self, suffix: &[u8]) -> KnotHashConfig {
+        self.suffix = suffix.to_vec();
+        self
+    }
+
+    pub fn block_size(mut self, block_size: usize) -> KnotHashConfig {
+        assert!(block_size > 0, "block size must be at least 1");
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn hash(&self, input: &[u8]) -> KnotHash {
+        let lengths = get_lengths(input, &self.suffix);
+        let numbers = sparse_hash(self.ring_size, &lengths, self.rounds);
+        let hash = dense_hash(&numbers[..self.ring_size], self.block_size);
+
+        KnotHash { value: hash }
+    }
+
+    // The number of bytes in a hash made with this configuration: one for
+    // each block of the ring, counting a shorter last block.
+    pub fn hash_len(&self) -> usize {
+        self.ring_size.div_ceil(self.block_size)
+    }
+
+    // Reads back a hash printed in hex, which must have two digits for each
+    // byte of a hash made with this configuration.
+    pub fn parse(&self, s: &str) -> Result<KnotHash, ParseKnotHashError> {
+        if s.len() != 2 * self.hash_len() {
+            return Err(ParseKnotHashError::invalid_length());
+        }
+
+        let digits = s.chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(ParseKnotHashError::invalid_digit)?;
+
+        let value = digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect();
+
+        Ok(KnotHash { value })
+    }
+
+    pub fn hasher(&self) -> KnotHasher {
+        KnotHasher::with_config(self.clone())
+    }
+
+    pub fn hash_many<I>(&self, inputs: I) -> Vec<KnotHash>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        inputs
+            .into_iter()
+            .map(|input| self.hash(input.as_ref()))
+            .collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_hash_all<I>(&self, inputs: I) -> Vec<KnotHash>
+    where
+        I: IntoParallelIterator,
+        I::Iter: IndexedParallelIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        inputs
+            .into_par_iter()
+            .map(|input| self.hash(input.as_ref()))
+            .collect()
+    }
+}
+
+pub fn hash_many<I>(inputs: I) -> Vec<KnotHash>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    KnotHashConfig::default().hash_many(inputs)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct KnotHash {
     value: Vec<u8>,
 }
 
 impl KnotHash {
     pub fn new(input: &str) -> KnotHash {
-        let lengths = get_lengths(input);
-        let numbers: Vec<u8> = (0..256).map(|n| n as u8).collect();
-        let hash = dense_hash(&sparse_hash(&numbers, &lengths));
+        KnotHash::from_bytes(input.as_bytes())
+    }
 
-        KnotHash { value: hash }
+    pub fn from_bytes(input: &[u8]) -> KnotHash {
+        KnotHashConfig::default().hash(input)
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_hash_all<I>(inputs: I) -> Vec<KnotHash>
+    where
+        I: IntoParallelIterator,
+        I::Iter: IndexedParallelIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        KnotHashConfig::default().par_hash_all(inputs)
     }
 
     pub fn as_bytes(&self) -> &[u8] {
         self.value.as_slice()
     }
+
+    // Only hashes produced with the default block size fit in 16 bytes.
+    pub fn to_array(&self) -> Option<[u8; 16]> {
+        if self.value.len() != 16 {
+            return None;
+        }
+
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&self.value);
+        Some(bytes)
+    }
+}
+
+impl From<[u8; 16]> for KnotHash {
+    fn from(bytes: [u8; 16]) -> KnotHash {
+        KnotHash {
+            value: bytes.to_vec(),
+        }
+    }
+}
+
+impl FromStr for KnotHash {
+    type Err = ParseKnotHashError;
+
+    // Only accepts the 32 digits of a hash made with the default
+    // configuration; `KnotHashConfig::parse` reads any other kind.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KnotHashConfig::default().parse(s)
+    }
 }
 
 impl fmt::Display for KnotHash {
@@ -84,6 +277,73 @@ impl fmt::Display for KnotHash {
     }
 }
 
+// Every round of the knot reapplies the complete sequence of lengths, so the
+// input can only be buffered until the hash is finalized.
+#[derive(Debug, Clone, Default)]
+pub struct KnotHasher {
+    config: KnotHashConfig,
+    input: Vec<u8>,
+    // What `finish` returned, until more input arrives.
+    finished: Cell<Option<u64>>,
+}
+
+impl KnotHasher {
+    pub fn new() -> KnotHasher {
+        KnotHasher::with_config(KnotHashConfig::default())
+    }
+
+    pub fn with_config(config: KnotHashConfig) -> KnotHasher {
+        KnotHasher {
+            config,
+            input: Vec::new(),
+            finished: Cell::new(None),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.input.extend_from_slice(bytes);
+        self.finished.set(None);
+    }
+
+    pub fn finalize(self) -> KnotHash {
+        self.config.hash(&self.input)
+    }
+}
+
+impl Hasher for KnotHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    // Hashing runs every round over all of the input, so the result is kept
+    // for any further calls before the next write.
+    fn finish(&self) -> u64 {
+        if let Some(finished) = self.finished.get() {
+            return finished;
+        }
+
+        let finished = self.config
+            .hash(&self.input)
+            .as_bytes()
+            .iter()
+            .take(8)
+            .fold(0, |acc, &byte| (acc << 8) | u64::from(byte));
+        self.finished.set(Some(finished));
+        finished
+    }
+}
+
+impl io::Write for KnotHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,7 +368,7 @@ mod tests {
 
     #[test]
     fn get_lengths_converts_string_correctly() {
-        let lengths = get_lengths("1,2,3");
+        let lengths = get_lengths(b"1,2,3", &DEFAULT_SUFFIX);
         assert_eq!(lengths, vec![49, 44, 50, 44, 51, 17, 31, 73, 47, 23]);
     }
 
@@ -121,11 +381,11 @@ mod tests {
     #[test]
     fn knot_hash_formatting() {
         let hash = KnotHash {
-            value: vec![64, 7, 255]
+            value: vec![64, 7, 255],
         };
 
         assert_eq!(hash.to_string(), "4007ff");
-    }    
+    }
 
     #[test]
     fn hash_empty_string() {
@@ -145,9 +405,242 @@ mod tests {
         assert_eq!(hash, "3efbe78a8d82f29979031a4aa0b16a9d");
     }
 
-     #[test]
+    #[test]
     fn hash_124() {
         let hash = KnotHash::new("1,2,4").to_string();
         assert_eq!(hash, "63960835bcdc130f0b66d7ff4f6a5a8e");
     }
+
+    #[test]
+    fn hasher_accepts_input_in_chunks() {
+        let mut hasher = KnotHasher::new();
+        hasher.update(b"AoC");
+        hasher.update(b"");
+        hasher.update(b" 20");
+        hasher.update(b"17");
+
+        assert_eq!(
+            hasher.finalize().to_string(),
+            "33efeb34ea91902bb2f59c9920caa6cd"
+        );
+    }
+
+    #[test]
+    fn hasher_as_io_writer() {
+        let mut hasher = KnotHasher::new();
+        let mut reader: &[u8] = b"1,2,4";
+        io::copy(&mut reader, &mut hasher).unwrap();
+
+        assert_eq!(
+            hasher.finalize().to_string(),
+            "63960835bcdc130f0b66d7ff4f6a5a8e"
+        );
+    }
+
+    #[test]
+    fn hasher_finish_uses_leading_bytes_of_hash() {
+        let mut hasher = KnotHasher::new();
+        hasher.write(b"1,2,3");
+
+        assert_eq!(hasher.finish(), 0x3efb_e78a_8d82_f299);
+    }
+
+    #[test]
+    fn hasher_finish_again_after_more_input() {
+        let mut hasher = KnotHasher::new();
+        hasher.write(b"1,2");
+        let first = hasher.finish();
+        assert_eq!(hasher.finish(), first);
+
+        hasher.write(b",3");
+        assert_eq!(hasher.finish(), 0x3efb_e78a_8d82_f299);
+    }
+
+    #[test]
+    fn hasher_in_hash_map() {
+        use std::collections::HashMap;
+        use std::hash::BuildHasherDefault;
+
+        let mut map: HashMap<&str, usize, BuildHasherDefault<KnotHasher>> = HashMap::default();
+        map.insert("flqrgnkx", 1);
+        map.insert("wenycdww", 2);
+
+        assert_eq!(map.get("flqrgnkx"), Some(&1));
+        assert_eq!(map.get("wenycdww"), Some(&2));
+        assert_eq!(map.get("AoC 2017"), None);
+    }
+
+    #[test]
+    fn single_round_example() {
+        let hash = KnotHashConfig::new()
+            .ring_size(5)
+            .rounds(1)
+            .suffix(&[])
+            .block_size(1)
+            .hash(&[3, 4, 1, 5]);
+
+        assert_eq!(hash.as_bytes(), &[3, 4, 2, 1, 0]);
+    }
+
+    #[test]
+    fn default_config_matches_knot_hash() {
+        let hash = KnotHashConfig::default().hash(b"AoC 2017");
+        assert_eq!(hash.to_string(), KnotHash::new("AoC 2017").to_string());
+    }
+
+    #[test]
+    fn block_size_sets_hash_width() {
+        let hash = KnotHashConfig::new().block_size(8).hash(b"AoC 2017");
+        assert_eq!(hash.as_bytes().len(), 32);
+    }
+
+    #[test]
+    fn lengths_longer_than_ring_are_ignored() {
+        let mut numbers: Vec<u8> = (0..5).collect();
+        knot(&mut numbers, &[3, 6, 4], &mut 0, &mut 0);
+        assert_eq!(numbers, vec![4, 3, 0, 1, 2]);
+    }
+
+    #[test]
+    fn hasher_with_config() {
+        let config = KnotHashConfig::new().ring_size(5).rounds(1).suffix(&[]).block_size(1);
+        let mut hasher = config.hasher();
+        hasher.update(&[3, 4]);
+        hasher.update(&[1, 5]);
+
+        assert_eq!(hasher.finalize().as_bytes(), &[3, 4, 2, 1, 0]);
+    }
+
+    #[test]
+    fn parse_hash() {
+        let hash: KnotHash = "33efeb34ea91902bb2f59c9920caa6cd".parse().unwrap();
+        assert_eq!(hash, KnotHash::new("AoC 2017"));
+    }
+
+    #[test]
+    fn parse_upper_case_hash() {
+        let hash: KnotHash = "3EFBE78A8D82F29979031A4AA0B16A9D".parse().unwrap();
+        assert_eq!(hash, KnotHash::new("1,2,3"));
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let hash = KnotHash::new("wenycdww");
+        let parsed: KnotHash = hash.to_string().parse().unwrap();
+        assert_eq!(parsed, hash);
+    }
+
+    #[test]
+    fn parse_odd_length_fails() {
+        let error = "4007f".parse::<KnotHash>().unwrap_err();
+        assert_eq!(error.kind, errors::ErrorKind::InvalidLength);
+    }
+
+    #[test]
+    fn parse_empty_string_fails() {
+        let error = "".parse::<KnotHash>().unwrap_err();
+        assert_eq!(error.kind, errors::ErrorKind::InvalidLength);
+    }
+
+    #[test]
+    fn parse_wrong_length_fails() {
+        let digest = "33efeb34ea91902bb2f59c9920caa6cd";
+        for s in &[&digest[..30], &format!("{}00", digest)] {
+            let error = s.parse::<KnotHash>().unwrap_err();
+            assert_eq!(error.kind, errors::ErrorKind::InvalidLength);
+        }
+
+        let error = "ab".parse::<KnotHash>().unwrap_err();
+        assert_eq!(error.kind, errors::ErrorKind::InvalidLength);
+    }
+
+    #[test]
+    fn parse_invalid_digit_fails() {
+        let error = "4007fg4007fg4007fg4007fg4007fg40".parse::<KnotHash>().unwrap_err();
+        assert_eq!(error.kind, errors::ErrorKind::InvalidDigit);
+
+        let error = "+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1".parse::<KnotHash>().unwrap_err();
+        assert_eq!(error.kind, errors::ErrorKind::InvalidDigit);
+    }
+
+    #[test]
+    fn parse_with_config() {
+        let config = KnotHashConfig::new().block_size(8);
+        let hash = config.hash(b"AoC 2017");
+        assert_eq!(config.hash_len(), 32);
+        assert_eq!(config.parse(&hash.to_string()).unwrap(), hash);
+        assert!(hash.to_string().parse::<KnotHash>().is_err());
+
+        let config = KnotHashConfig::new().ring_size(5).block_size(2);
+        assert_eq!(config.hash_len(), 3);
+        assert!(config.parse("0a0b0c").is_ok());
+    }
+
+    #[test]
+    fn hashes_are_ordered_by_bytes() {
+        let low = KnotHash { value: vec![0, 255] };
+        let high = KnotHash { value: vec![1, 0] };
+        assert!(low < high);
+    }
+
+    #[test]
+    fn to_array_of_default_hash() {
+        let hash = KnotHash::new("");
+        let bytes = hash.to_array().unwrap();
+        assert_eq!(KnotHash::from(bytes), hash);
+    }
+
+    #[test]
+    fn to_array_of_wide_hash() {
+        let hash = KnotHashConfig::new().block_size(8).hash(b"");
+        assert_eq!(hash.to_array(), None);
+    }
+
+    #[test]
+    fn reverse_segment_wrapping_odd_length() {
+        let mut numbers: Vec<u8> = (0..5).collect();
+        reverse_segment(&mut numbers, 4, 5);
+        assert_eq!(numbers, vec![2, 1, 0, 4, 3]);
+    }
+
+    #[test]
+    fn reverse_whole_ring() {
+        let mut numbers: Vec<u8> = (0..4).collect();
+        reverse_segment(&mut numbers, 1, 4);
+        assert_eq!(numbers, vec![1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn sparse_hash_uses_only_ring_size_entries() {
+        let numbers = sparse_hash(5, &[3, 4, 1, 5], 1);
+        assert_eq!(numbers[..5], [3, 4, 2, 1, 0]);
+        assert_eq!(numbers[5], 5);
+    }
+
+    #[test]
+    fn hash_many_preserves_order() {
+        let hashes = hash_many(vec!["", "AoC 2017", "1,2,3"]);
+        let expected: Vec<KnotHash> = ["", "AoC 2017", "1,2,3"]
+            .iter()
+            .map(|input| KnotHash::new(input))
+            .collect();
+
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn hash_many_with_config() {
+        let config = KnotHashConfig::new().ring_size(5).rounds(1).suffix(&[]).block_size(1);
+        let hashes = config.hash_many(vec![vec![3u8, 4, 1, 5], vec![]]);
+
+        assert_eq!(hashes[0].as_bytes(), &[3, 4, 2, 1, 0]);
+        assert_eq!(hashes[1].as_bytes(), &[0, 1, 2, 3, 4]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_hash_all_matches_hash_many() {
+        let inputs: Vec<String> = (0..128).map(|n| format!("flqrgnkx-{}", n)).collect();
+        assert_eq!(KnotHash::par_hash_all(inputs.clone()), hash_many(inputs));
+    }
 }
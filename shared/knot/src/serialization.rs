@@ -0,0 +1,58 @@
+use super::KnotHash;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+impl Serialize for KnotHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct KnotHashVisitor;
+
+impl<'de> Visitor<'de> for KnotHashVisitor {
+    type Value = KnotHash;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a knot hash as a string of hex digits")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<KnotHash, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for KnotHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<KnotHash, D::Error> {
+        deserializer.deserialize_str(KnotHashVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::super::KnotHash;
+
+    #[test]
+    fn serializes_as_hex_string() {
+        let hash = KnotHash::new("AoC 2017");
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, "\"33efeb34ea91902bb2f59c9920caa6cd\"");
+    }
+
+    #[test]
+    fn round_trip_through_json() {
+        let hash = KnotHash::new("1,2,3");
+        let json = serde_json::to_string(&hash).unwrap();
+        let parsed: KnotHash = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, hash);
+    }
+
+    #[test]
+    fn deserialize_rejects_invalid_hex() {
+        let parsed: Result<KnotHash, _> = serde_json::from_str("\"not a hash\"");
+        assert!(parsed.is_err());
+    }
+}
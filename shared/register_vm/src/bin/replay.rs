@@ -0,0 +1,114 @@
+extern crate register_vm;
+use register_vm::{Divergence, Event, Register, Trace};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+
+const USAGE: &str = "Usage: replay TRACE [STEP]
+       replay diff LEFT RIGHT
+Inspect execution traces written as JSON Lines.
+
+  replay TRACE         summarize a trace and show the final registers
+  replay TRACE STEP    show the instruction executed at STEP, and the
+                       registers before and after it
+  replay diff A B      find the first step at which two traces differ";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("ERROR: {}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    match args[..] {
+        ["-h"] | ["--help"] => println!("{}", USAGE),
+        ["diff", left, right] => diff(&load(left)?, &load(right)?),
+        [path] => summarize(&load(path)?),
+        [path, step] => {
+            let step: usize = step
+                .parse()
+                .map_err(|_| format!("Invalid step '{}'\n{}", step, USAGE))?;
+            seek(&load(path)?, step)?;
+        }
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn load(path: &str) -> Result<Trace, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    Trace::read(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+fn format_registers(registers: &BTreeMap<Register, i64>) -> String {
+    if registers.is_empty() {
+        return "(all zero)".to_string();
+    }
+
+    registers
+        .iter()
+        .map(|(r, v)| format!("{} = {}", r, v))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn format_event(event: &Event) -> String {
+    format!(
+        "step {}: {:>4}: {:<16} {}",
+        event.step,
+        event.pc,
+        event.instruction,
+        format_registers(&event.delta)
+    )
+}
+
+fn summarize(trace: &Trace) {
+    println!("{} instructions executed", trace.len());
+    println!("Initial registers: {}", format_registers(trace.initial_registers()));
+    println!("Final registers:   {}", format_registers(&trace.registers_at(trace.len())));
+}
+
+fn seek(trace: &Trace, step: usize) -> Result<(), String> {
+    let event = trace.events().get(step).ok_or_else(|| {
+        format!("Step {} is past the end of the trace ({} steps)", step, trace.len())
+    })?;
+
+    println!("Before: {}", format_registers(&trace.registers_at(step)));
+    println!("{}", format_event(event));
+    println!("After:  {}", format_registers(&trace.registers_at(step + 1)));
+    Ok(())
+}
+
+fn diff(left: &Trace, right: &Trace) {
+    match left.first_divergence(right) {
+        None => println!("The traces are identical ({} steps).", left.len()),
+        Some(Divergence::InitialRegisters) => {
+            println!("The traces start from different registers:");
+            println!("< {}", format_registers(left.initial_registers()));
+            println!("> {}", format_registers(right.initial_registers()));
+        }
+        Some(Divergence::Step { step, left: l, right: r }) => {
+            println!("The traces diverge at step {}.", step);
+            if step > 0 {
+                println!(
+                    "Registers until then: {}",
+                    format_registers(&left.registers_at(step as usize))
+                );
+            }
+
+            let show = |event: Option<Event>| match event {
+                Some(event) => format_event(&event),
+                None => "(trace ends)".to_string(),
+            };
+            println!("< {}", show(l));
+            println!("> {}", show(r));
+        }
+    }
+}
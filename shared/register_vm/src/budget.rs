@@ -0,0 +1,201 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+// How often the clock is read, in steps, since reading it is slow compared
+// with executing an instruction.
+const CLOCK_INTERVAL: u64 = 1024;
+
+// Limits on how long a program may run. The default has no limits.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct Budget {
+    pub max_steps: Option<u64>,
+    pub max_time: Option<Duration>,
+    // Whether to stop a program that gets back into a state it has already
+    // been in, which means that it will never finish.
+    pub detect_loops: bool,
+}
+
+// Why a program was stopped before it finished.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Halted {
+    StepLimit,
+    TimeLimit,
+    // The state after `first_seen` steps recurs every `period` steps.
+    LoopDetected { first_seen: u64, period: u64 },
+}
+
+impl fmt::Display for Halted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Halted::StepLimit => write!(f, "Step limit reached"),
+            Halted::TimeLimit => write!(f, "Time limit reached"),
+            Halted::LoopDetected { first_seen, period } => write!(
+                f,
+                "Infinite loop: the state after step {} recurs every {} steps",
+                first_seen, period
+            ),
+        }
+    }
+}
+
+impl error::Error for Halted {
+    fn description(&self) -> &str {
+        "Program stopped"
+    }
+}
+
+// Enforces a budget on a running program, which must call `tick` before
+// every step.
+//
+// Loops are found with Brent's algorithm: the state is remembered after 0, 1,
+// 2, 4, 8... steps and compared with every later state, so only one state is
+// ever stored. States are compared by hash, so a collision could in theory
+// report a loop that is not there.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    budget: Budget,
+    steps: u64,
+    started: Instant,
+    checkpoint: Option<(u64, u64)>,
+    next_checkpoint: u64,
+}
+
+impl Watchdog {
+    pub fn new(budget: &Budget) -> Watchdog {
+        Watchdog {
+            budget: *budget,
+            steps: 0,
+            started: Instant::now(),
+            checkpoint: None,
+            next_checkpoint: 0,
+        }
+    }
+
+    // The number of steps allowed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Checks whether the program may take another step. `state` should
+    // include everything that affects what the program does from here on; it
+    // is only looked at if loop detection is on.
+    pub fn tick<S: Hash + ?Sized>(&mut self, state: &S) -> Result<(), Halted> {
+        if let Some(max_steps) = self.budget.max_steps {
+            if self.steps >= max_steps {
+                return Err(Halted::StepLimit);
+            }
+        }
+
+        if let Some(max_time) = self.budget.max_time {
+            if self.steps.is_multiple_of(CLOCK_INTERVAL) && self.started.elapsed() >= max_time {
+                return Err(Halted::TimeLimit);
+            }
+        }
+
+        if self.budget.detect_loops {
+            let mut hasher = DefaultHasher::new();
+            state.hash(&mut hasher);
+            let hash = hasher.finish();
+
+            if let Some((seen, first_seen)) = self.checkpoint {
+                if seen == hash {
+                    return Err(Halted::LoopDetected {
+                        first_seen,
+                        period: self.steps - first_seen,
+                    });
+                }
+            }
+
+            if self.steps == self.next_checkpoint {
+                self.checkpoint = Some((hash, self.steps));
+                self.next_checkpoint = (self.steps * 2).max(1);
+            }
+        }
+
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts through `states`, then around the loop that starts at `cycle`
+    // forever.
+    fn run(states: &[u32], cycle: usize, budget: &Budget) -> Halted {
+        let mut watchdog = Watchdog::new(budget);
+        let mut index = 0;
+        loop {
+            if let Err(halted) = watchdog.tick(&states[index]) {
+                return halted;
+            }
+
+            index = if index + 1 == states.len() { cycle } else { index + 1 };
+        }
+    }
+
+    #[test]
+    fn step_limit() {
+        let budget = Budget {
+            max_steps: Some(10),
+            ..Budget::default()
+        };
+        let mut watchdog = Watchdog::new(&budget);
+        for _ in 0..10 {
+            assert_eq!(watchdog.tick(&0), Ok(()));
+        }
+        assert_eq!(watchdog.tick(&0), Err(Halted::StepLimit));
+        assert_eq!(watchdog.steps(), 10);
+    }
+
+    #[test]
+    fn time_limit() {
+        let budget = Budget {
+            max_time: Some(Duration::from_millis(1)),
+            ..Budget::default()
+        };
+        assert_eq!(run(&[0], 0, &budget), Halted::TimeLimit);
+    }
+
+    #[test]
+    fn find_loop_period() {
+        let budget = Budget {
+            detect_loops: true,
+            ..Budget::default()
+        };
+
+        let states: Vec<u32> = (0..20).collect();
+        for &cycle in &[0, 1, 7, 19] {
+            match run(&states, cycle, &budget) {
+                Halted::LoopDetected { first_seen, period } => {
+                    assert_eq!(period, 20 - cycle as u64);
+                    assert!(first_seen >= cycle as u64);
+                }
+                halted => panic!("expected a loop, got {:?}", halted),
+            }
+        }
+    }
+
+    #[test]
+    fn distinct_states_are_not_a_loop() {
+        let budget = Budget {
+            max_steps: Some(10_000),
+            detect_loops: true,
+            ..Budget::default()
+        };
+        let mut watchdog = Watchdog::new(&budget);
+        let mut state = 0u64;
+        let halted = loop {
+            if let Err(halted) = watchdog.tick(&state) {
+                break halted;
+            }
+            state += 1;
+        };
+
+        assert_eq!(halted, Halted::StepLimit);
+    }
+}
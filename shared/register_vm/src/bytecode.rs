@@ -0,0 +1,65 @@
+use super::{Register, Value};
+
+pub const REGISTER_COUNT: usize = 26;
+
+// The position of a register in `Registers`: 0 for a, up to 25 for z.
+pub type Slot = u8;
+
+pub fn slot(register: Register) -> Option<Slot> {
+    if register.is_ascii_lowercase() {
+        Some(register as u8 - b'a')
+    } else {
+        None
+    }
+}
+
+pub fn register_name(slot: Slot) -> Register {
+    char::from(b'a' + slot)
+}
+
+// The slot for a register named in a parsed instruction. `parse` rejects any
+// register that does not have one, so this only panics for instructions
+// built by hand.
+pub fn compile_register(register: Register) -> Slot {
+    match slot(register) {
+        Some(slot) => slot,
+        None => panic!("register '{}' is not a lower case letter", register),
+    }
+}
+
+// A compiled instruction argument.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Source {
+    Register(Slot),
+    Literal(i64),
+}
+
+impl Source {
+    pub fn compile(value: &Value) -> Source {
+        match *value {
+            Value::FromRegister(r) => Source::Register(compile_register(r)),
+            Value::Literal(n) => Source::Literal(n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots() {
+        assert_eq!(slot('a'), Some(0));
+        assert_eq!(slot('z'), Some(25));
+        assert_eq!(slot('A'), None);
+        assert_eq!(register_name(7), 'h');
+        assert_eq!(Source::compile(&Value::FromRegister('c')), Source::Register(2));
+        assert_eq!(Source::compile(&Value::Literal(-4)), Source::Literal(-4));
+    }
+
+    #[test]
+    #[should_panic]
+    fn compile_register_without_a_slot() {
+        compile_register('?');
+    }
+}
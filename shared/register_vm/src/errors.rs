@@ -0,0 +1,85 @@
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    UnrecognizedInstruction,
+    MissingArgument,
+    WrongArgumentType,
+    ExpectedLiteral,
+    InvalidRegister,
+}
+
+// A line that could not be parsed. `token` is the position of the offending
+// word on the line, where the mnemonic is 0 and its arguments follow; for a
+// missing argument it is the position that argument should have had.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub token: usize,
+}
+
+impl Error {
+    pub fn missing_argument(token: usize) -> Error {
+        Error {
+            kind: ErrorKind::MissingArgument,
+            token,
+        }
+    }
+
+    pub fn unrecognized_instruction() -> Error {
+        Error {
+            kind: ErrorKind::UnrecognizedInstruction,
+            token: 0,
+        }
+    }
+
+    pub fn wrong_type(token: usize) -> Error {
+        Error {
+            kind: ErrorKind::WrongArgumentType,
+            token,
+        }
+    }
+
+    pub fn expected_literal(token: usize) -> Error {
+        Error {
+            kind: ErrorKind::ExpectedLiteral,
+            token,
+        }
+    }
+
+    pub fn invalid_register(token: usize) -> Error {
+        Error {
+            kind: ErrorKind::InvalidRegister,
+            token,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::UnrecognizedInstruction => write!(f, "Unrecognized instruction"),
+            ErrorKind::MissingArgument => write!(f, "Missing argument"),
+            ErrorKind::WrongArgumentType => {
+                write!(f, "Literal value supplied where register required")
+            }
+            ErrorKind::ExpectedLiteral => {
+                write!(f, "Register supplied where literal value required")
+            }
+            ErrorKind::InvalidRegister => write!(f, "Registers must be lower case letters"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "Invalid instruction"
+    }
+}
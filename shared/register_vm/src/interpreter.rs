@@ -0,0 +1,393 @@
+use budget::{Budget, Halted, Watchdog};
+use registers::Registers;
+use std::convert::TryFrom;
+use std::fmt;
+use trace::Trace;
+
+// Where execution goes after an instruction.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Control {
+    Next,
+    // Relative to the instruction that was executed.
+    Jump(i64),
+    // Stay on this instruction and try it again later.
+    Wait,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    Ran,
+    Waiting,
+    Halted,
+}
+
+// The semantics of one instruction set. `State` holds anything beyond the
+// registers that its instructions need, such as message queues or counters.
+// Instructions are lowered to `Op`s once, when the interpreter is built, and
+// it is those that get executed.
+pub trait Execute {
+    type State;
+    type Error;
+    type Op;
+
+    fn compile(&self) -> Self::Op;
+
+    fn execute(op: &Self::Op, registers: &mut Registers, state: &mut Self::State)
+        -> Result<Control, Self::Error>;
+
+    // Called when an instruction jumps to before the start of the program.
+    // An error leaves the program counter where it was; Ok halts the
+    // program, as jumping past the end does.
+    fn jump_before_start() -> Result<(), Self::Error>;
+}
+
+fn describe<I: fmt::Display>(instruction: &I) -> String {
+    instruction.to_string()
+}
+
+// A trace being recorded, and how to print each instruction for it.
+type Tracing<I> = (Trace, fn(&I) -> String);
+
+pub struct Interpreter<'a, I: 'a + Execute> {
+    instructions: &'a [I],
+    ops: Vec<I::Op>,
+    registers: Registers,
+    program_counter: usize,
+    steps: u64,
+    state: I::State,
+    tracing: Option<Tracing<I>>,
+}
+
+impl<'a, I: Execute> Interpreter<'a, I> {
+    pub fn new(instructions: &'a [I], state: I::State) -> Interpreter<'a, I> {
+        Interpreter {
+            instructions,
+            ops: instructions.iter().map(Execute::compile).collect(),
+            registers: Registers::new(),
+            program_counter: 0,
+            steps: 0,
+            state,
+            tracing: None,
+        }
+    }
+
+    pub fn instructions(&self) -> &'a [I] {
+        self.instructions
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub fn state(&self) -> &I::State {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut I::State {
+        &mut self.state
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    pub fn current_instruction(&self) -> Option<&'a I> {
+        self.instructions.get(self.program_counter)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.program_counter >= self.instructions.len()
+    }
+
+    // The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Moves on to the next instruction without executing the current one.
+    pub fn skip(&mut self) {
+        if !self.is_halted() {
+            self.program_counter += 1;
+        }
+    }
+
+    // Executes the current instruction. If it fails, the program counter
+    // stays where it was. Jumping past the end halts the program, and so may
+    // jumping to before the start, depending on the instruction set.
+    pub fn step(&mut self) -> Result<Outcome, I::Error> {
+        let op = match self.ops.get(self.program_counter) {
+            Some(op) => op,
+            None => return Ok(Outcome::Halted),
+        };
+
+        let offset = match I::execute(op, &mut self.registers, &mut self.state)? {
+            Control::Next => 1,
+            Control::Jump(offset) => offset,
+            Control::Wait => return Ok(Outcome::Waiting),
+        };
+
+        let target = match (self.program_counter as i64).checked_add(offset) {
+            Some(target) if target < 0 => {
+                I::jump_before_start()?;
+                usize::MAX
+            }
+            Some(target) => usize::try_from(target).unwrap_or(usize::MAX),
+            // The program counter is never negative, so only a jump forwards
+            // can overflow, and that goes past the end.
+            None => usize::MAX,
+        };
+
+        if let Some((ref mut trace, describe)) = self.tracing {
+            let instruction = &self.instructions[self.program_counter];
+            trace.record(self.program_counter, describe(instruction), &self.registers);
+        }
+
+        self.program_counter = target;
+        self.steps += 1;
+
+        Ok(if self.is_halted() {
+            Outcome::Halted
+        } else {
+            Outcome::Ran
+        })
+    }
+
+    // Stops recording and returns everything executed since `start_trace`.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.tracing.take().map(|(trace, _)| trace)
+    }
+
+    // Runs until the program halts or has to wait.
+    pub fn run(&mut self) -> Result<Outcome, I::Error> {
+        loop {
+            match self.step()? {
+                Outcome::Ran => {}
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+
+    // Like `run`, but gives up with `Halted` if the program exceeds the
+    // budget, leaving it on the instruction it would have executed next.
+    // Loops are found from the program counter and registers alone, so this
+    // suits instruction sets whose `State` does not affect where they jump.
+    pub fn run_with_budget(&mut self, budget: &Budget) -> Result<Outcome, I::Error>
+    where
+        I::Error: From<Halted>,
+    {
+        let mut watchdog = Watchdog::new(budget);
+
+        while !self.is_halted() {
+            watchdog.tick(&(self.program_counter, &self.registers))?;
+            match self.step()? {
+                Outcome::Ran => {}
+                outcome => return Ok(outcome),
+            }
+        }
+
+        Ok(Outcome::Halted)
+    }
+}
+
+impl<'a, I: Execute + fmt::Display> Interpreter<'a, I> {
+    // Records every instruction executed from now on, starting from the
+    // current registers. Restarts the trace if one is already being recorded.
+    pub fn start_trace(&mut self) {
+        self.tracing = Some((Trace::new(&self.registers), describe::<I>));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Register;
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum ToyError {
+        JumpOutOfRange,
+        Halted(Halted),
+    }
+
+    impl From<Halted> for ToyError {
+        fn from(halted: Halted) -> ToyError {
+            ToyError::Halted(halted)
+        }
+    }
+
+    // Counts down register n, adding each value to register t, then waits
+    // for someone to set register w.
+    #[derive(Clone, Copy)]
+    enum Toy {
+        Add(Register, Register),
+        Decrement(Register),
+        JumpIfPositive(Register, i64),
+        WaitFor(Register),
+    }
+
+    impl Execute for Toy {
+        type State = u32;
+        type Error = ToyError;
+        type Op = Toy;
+
+        fn compile(&self) -> Toy {
+            *self
+        }
+
+        fn execute(op: &Toy, registers: &mut Registers, waits: &mut u32) -> Result<Control, ToyError> {
+            Ok(match *op {
+                Toy::Add(x, y) => {
+                    *registers.get_mut(x) += registers.get(y);
+                    Control::Next
+                }
+                Toy::Decrement(x) => {
+                    *registers.get_mut(x) -= 1;
+                    Control::Next
+                }
+                Toy::JumpIfPositive(x, offset) if registers.get(x) > 0 => Control::Jump(offset),
+                Toy::JumpIfPositive(_, _) => Control::Next,
+                Toy::WaitFor(x) if registers.get(x) == 0 => {
+                    *waits += 1;
+                    Control::Wait
+                }
+                Toy::WaitFor(_) => Control::Next,
+            })
+        }
+
+        fn jump_before_start() -> Result<(), ToyError> {
+            Err(ToyError::JumpOutOfRange)
+        }
+    }
+
+    #[test]
+    fn run_until_waiting_then_halt() {
+        let program = vec![
+            Toy::Add('t', 'n'),
+            Toy::Decrement('n'),
+            Toy::JumpIfPositive('n', -2),
+            Toy::WaitFor('w'),
+        ];
+        let mut interpreter = Interpreter::new(&program, 0);
+        interpreter.registers_mut().set('n', 4);
+
+        assert_eq!(interpreter.run(), Ok(Outcome::Waiting));
+        assert_eq!(interpreter.registers().get('t'), 10);
+        assert_eq!(interpreter.program_counter(), 3);
+        assert_eq!(interpreter.steps(), 12);
+        assert_eq!(*interpreter.state(), 1);
+
+        interpreter.registers_mut().set('w', 1);
+        assert_eq!(interpreter.run(), Ok(Outcome::Halted));
+        assert!(interpreter.is_halted());
+    }
+
+    impl fmt::Display for Toy {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Toy::Add(x, y) => write!(f, "add {} {}", x, y),
+                Toy::Decrement(x) => write!(f, "dec {}", x),
+                Toy::JumpIfPositive(x, offset) => write!(f, "jgz {} {}", x, offset),
+                Toy::WaitFor(x) => write!(f, "wait {}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn trace_executed_instructions() {
+        let program = vec![Toy::Add('t', 'n'), Toy::Decrement('n'), Toy::WaitFor('w')];
+        let mut interpreter = Interpreter::new(&program, 0);
+        interpreter.registers_mut().set('n', 2);
+        interpreter.step().unwrap();
+
+        interpreter.start_trace();
+        interpreter.run().unwrap();
+        let trace = interpreter.take_trace().unwrap();
+
+        // Waiting does not execute anything, so is not recorded.
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace.events()[0].pc, 1);
+        assert_eq!(trace.events()[0].instruction, "dec n");
+        assert_eq!(trace.registers_at(0).get(&'t'), Some(&2));
+        assert_eq!(trace.registers_at(1).get(&'n'), Some(&1));
+        assert!(interpreter.take_trace().is_none());
+    }
+
+    #[test]
+    fn jump_before_start() {
+        let program = vec![Toy::JumpIfPositive('n', -1)];
+        let mut interpreter = Interpreter::new(&program, 0);
+        interpreter.registers_mut().set('n', 1);
+
+        assert_eq!(interpreter.step(), Err(ToyError::JumpOutOfRange));
+        assert_eq!(interpreter.program_counter(), 0);
+    }
+
+    #[test]
+    fn huge_jump_past_the_end_halts() {
+        let program = vec![Toy::Decrement('n'), Toy::JumpIfPositive('n', i64::MAX)];
+        let mut interpreter = Interpreter::new(&program, 0);
+        interpreter.registers_mut().set('n', 2);
+
+        assert_eq!(interpreter.step(), Ok(Outcome::Ran));
+        assert_eq!(interpreter.step(), Ok(Outcome::Halted));
+        assert!(interpreter.is_halted());
+        assert_eq!(interpreter.steps(), 2);
+    }
+
+    // The same instructions, for an instruction set that halts on any jump
+    // out of the program.
+    struct Halting(Toy);
+
+    impl Execute for Halting {
+        type State = u32;
+        type Error = ToyError;
+        type Op = Toy;
+
+        fn compile(&self) -> Toy {
+            self.0
+        }
+
+        fn execute(op: &Toy, registers: &mut Registers, waits: &mut u32) -> Result<Control, ToyError> {
+            Toy::execute(op, registers, waits)
+        }
+
+        fn jump_before_start() -> Result<(), ToyError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn jump_before_start_can_halt() {
+        let program = vec![Halting(Toy::Decrement('n')), Halting(Toy::JumpIfPositive('n', -2))];
+        let mut interpreter = Interpreter::new(&program, 0);
+        interpreter.registers_mut().set('n', 2);
+
+        assert_eq!(interpreter.run(), Ok(Outcome::Halted));
+        assert_eq!(interpreter.registers().get('n'), 1);
+        assert_eq!(interpreter.steps(), 2);
+    }
+
+    #[test]
+    fn budget_stops_endless_programs() {
+        let program = vec![Toy::Decrement('n'), Toy::JumpIfPositive('w', -1)];
+        let budget = Budget {
+            max_steps: Some(10),
+            ..Budget::default()
+        };
+        let mut interpreter = Interpreter::new(&program, 0);
+        interpreter.registers_mut().set('w', 1);
+
+        assert_eq!(
+            interpreter.run_with_budget(&budget),
+            Err(ToyError::Halted(Halted::StepLimit))
+        );
+        assert_eq!(interpreter.registers().get('n'), -5);
+        assert_eq!(interpreter.program_counter(), 0);
+
+        interpreter.registers_mut().set('w', 0);
+        assert_eq!(interpreter.run_with_budget(&budget), Ok(Outcome::Halted));
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+extern crate proptest;
+extern crate serde;
+extern crate serde_json;
+
+mod budget;
+pub mod bytecode;
+pub mod errors;
+mod interpreter;
+mod parser;
+mod registers;
+mod trace;
+
+pub use budget::{Budget, Halted, Watchdog};
+pub use errors::{Error, ErrorKind};
+pub use interpreter::{Control, Execute, Interpreter, Outcome};
+pub use parser::{parse, Arguments, InstructionSet, Opcode, Operand};
+pub use registers::Registers;
+pub use trace::{Divergence, Event, Trace};
+use std::fmt;
+use std::str::FromStr;
+
+pub type Register = char;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Value {
+    FromRegister(Register),
+    Literal(i64),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::FromRegister(r) => write!(f, "{}", r),
+            Value::Literal(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+// Anything that parses as a number is a literal; otherwise the first
+// character names a register. Only a to z are registers, but that is left
+// for `parse` to check so that it can report where the bad register is.
+impl FromStr for Value {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(n) = s.parse() {
+            Ok(Value::Literal(n))
+        } else if let Some(c) = s.chars().next() {
+            Ok(Value::FromRegister(c))
+        } else {
+            Err(Error::missing_argument(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!("-12".parse(), Ok(Value::Literal(-12)));
+        assert_eq!("x".parse(), Ok(Value::FromRegister('x')));
+        assert!("".parse::<Value>().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn print_then_parse(value in prop_oneof![
+            (b'a'..=b'z').prop_map(|c| Value::FromRegister(char::from(c))),
+            any::<i64>().prop_map(Value::Literal),
+        ]) {
+            prop_assert_eq!(value.to_string().parse::<Value>().unwrap(), value);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Register, Value};
+use bytecode::slot;
+use errors::Error;
+
+// The kinds of argument an opcode can take.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Operand {
+    Register,
+    Literal,
+    // Either a register or a literal.
+    Value,
+}
+
+// The arguments of a parsed instruction, already checked against the
+// opcode's operands, so asking for the kind of argument the opcode declared
+// always succeeds.
+pub struct Arguments<'a> {
+    values: &'a [Value],
+}
+
+impl<'a> Arguments<'a> {
+    pub fn register(&self, n: usize) -> Register {
+        match self.values[n] {
+            Value::FromRegister(r) => r,
+            Value::Literal(_) => panic!("argument {} is not a register", n),
+        }
+    }
+
+    pub fn literal(&self, n: usize) -> i64 {
+        match self.values[n] {
+            Value::Literal(v) => v,
+            Value::FromRegister(_) => panic!("argument {} is not a literal", n),
+        }
+    }
+
+    pub fn value(&self, n: usize) -> Value {
+        self.values[n].clone()
+    }
+}
+
+// One line of an instruction set's opcode table: the mnemonic, what it
+// expects after it, and how to build the instruction from those arguments.
+pub struct Opcode<I> {
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    pub build: fn(&Arguments) -> I,
+}
+
+pub trait InstructionSet: Sized + 'static {
+    fn opcodes() -> &'static [Opcode<Self>];
+}
+
+// Parses one line of assembly using an instruction set's opcode table. Any
+// words after the last argument are ignored.
+pub fn parse<I: InstructionSet>(line: &str) -> Result<I, Error> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mnemonic = match words.first() {
+        Some(mnemonic) => *mnemonic,
+        None => return Err(Error::missing_argument(0)),
+    };
+
+    let opcode = match I::opcodes().iter().find(|op| op.mnemonic == mnemonic) {
+        Some(opcode) => opcode,
+        None => return Err(Error::unrecognized_instruction()),
+    };
+
+    let mut values = Vec::with_capacity(opcode.operands.len());
+    for (n, operand) in opcode.operands.iter().enumerate() {
+        let token = n + 1;
+        let value: Value = match words.get(token) {
+            Some(word) => word.parse().map_err(|_| Error::missing_argument(token))?,
+            None => return Err(Error::missing_argument(token)),
+        };
+
+        match (*operand, &value) {
+            (Operand::Register, &Value::Literal(_)) => return Err(Error::wrong_type(token)),
+            (Operand::Literal, &Value::FromRegister(_)) => {
+                return Err(Error::expected_literal(token))
+            }
+            (_, &Value::FromRegister(r)) if slot(r).is_none() => {
+                return Err(Error::invalid_register(token))
+            }
+            _ => {}
+        }
+
+        values.push(value);
+    }
+
+    Ok((opcode.build)(&Arguments { values: &values }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ErrorKind;
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Toy {
+        Increment(Register),
+        Jump(Value, i64),
+    }
+
+    static TOY: &[Opcode<Toy>] = &[
+        Opcode {
+            mnemonic: "inc",
+            operands: &[Operand::Register],
+            build: |a| Toy::Increment(a.register(0)),
+        },
+        Opcode {
+            mnemonic: "jmp",
+            operands: &[Operand::Value, Operand::Literal],
+            build: |a| Toy::Jump(a.value(0), a.literal(1)),
+        },
+    ];
+
+    impl InstructionSet for Toy {
+        fn opcodes() -> &'static [Opcode<Toy>] {
+            TOY
+        }
+    }
+
+    #[test]
+    fn parse_with_table() {
+        assert_eq!(parse::<Toy>("inc a"), Ok(Toy::Increment('a')));
+        assert_eq!(
+            parse::<Toy>("  jmp b -3 "),
+            Ok(Toy::Jump(Value::FromRegister('b'), -3))
+        );
+    }
+
+    #[test]
+    fn report_bad_arguments() {
+        let error = |line: &str| {
+            let e = parse::<Toy>(line).unwrap_err();
+            (e.kind, e.token)
+        };
+
+        assert_eq!(error(""), (ErrorKind::MissingArgument, 0));
+        assert_eq!(error("dec a"), (ErrorKind::UnrecognizedInstruction, 0));
+        assert_eq!(error("inc"), (ErrorKind::MissingArgument, 1));
+        assert_eq!(error("inc 4"), (ErrorKind::WrongArgumentType, 1));
+        assert_eq!(error("jmp 1"), (ErrorKind::MissingArgument, 2));
+        assert_eq!(error("jmp 1 a"), (ErrorKind::ExpectedLiteral, 2));
+        assert_eq!(error("inc A"), (ErrorKind::InvalidRegister, 1));
+        assert_eq!(error("jmp ? 1"), (ErrorKind::InvalidRegister, 1));
+    }
+}
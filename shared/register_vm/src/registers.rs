@@ -0,0 +1,119 @@
+use super::{Register, Value};
+use bytecode::{compile_register, register_name, slot, Slot, Source, REGISTER_COUNT};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// Registers a to z, which all start out at zero. They are stored by slot, so
+// compiled instructions can reach them without hashing their names.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Registers {
+    values: [i64; REGISTER_COUNT],
+    // Bit n is set once the register in slot n has been written to.
+    written: u32,
+}
+
+impl Registers {
+    pub fn new() -> Registers {
+        Registers::default()
+    }
+
+    // Any register other than a to z can never have been written, so it
+    // reads as zero.
+    pub fn get(&self, register: Register) -> i64 {
+        slot(register).map_or(0, |slot| self.load(slot))
+    }
+
+    pub fn set(&mut self, register: Register, value: i64) {
+        self.store(compile_register(register), value);
+    }
+
+    pub fn get_mut(&mut self, register: Register) -> &mut i64 {
+        self.slot_mut(compile_register(register))
+    }
+
+    // The value of an instruction argument.
+    pub fn value(&self, value: &Value) -> i64 {
+        match *value {
+            Value::FromRegister(r) => self.get(r),
+            Value::Literal(n) => n,
+        }
+    }
+
+    #[inline]
+    pub fn load(&self, slot: Slot) -> i64 {
+        self.values[slot as usize]
+    }
+
+    #[inline]
+    pub fn store(&mut self, slot: Slot, value: i64) {
+        *self.slot_mut(slot) = value;
+    }
+
+    #[inline]
+    pub fn slot_mut(&mut self, slot: Slot) -> &mut i64 {
+        self.written |= 1 << slot;
+        &mut self.values[slot as usize]
+    }
+
+    // The value of a compiled instruction argument.
+    #[inline]
+    pub fn source(&self, source: Source) -> i64 {
+        match source {
+            Source::Register(slot) => self.load(slot),
+            Source::Literal(n) => n,
+        }
+    }
+
+    // Every register that has been written to, and its value.
+    pub fn to_map(&self) -> HashMap<Register, i64> {
+        (0..REGISTER_COUNT as Slot)
+            .filter(|slot| self.written & (1 << slot) != 0)
+            .map(|slot| (register_name(slot), self.load(slot)))
+            .collect()
+    }
+}
+
+// Hashes the values of the registers, but not which have been written to,
+// so that registers holding the same values hash the same.
+impl Hash for Registers {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.values.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers_start_at_zero() {
+        let mut registers = Registers::new();
+        assert_eq!(registers.get('a'), 0);
+
+        registers.set('a', 5);
+        *registers.get_mut('b') -= 2;
+        assert_eq!(registers.value(&Value::FromRegister('a')), 5);
+        assert_eq!(registers.value(&Value::Literal(7)), 7);
+        assert_eq!(registers.get('b'), -2);
+        assert_eq!(registers.get('A'), 0);
+        assert_eq!(registers.to_map().len(), 2);
+    }
+
+    #[test]
+    fn access_by_slot() {
+        let mut registers = Registers::new();
+        registers.store(1, 4);
+        *registers.slot_mut(1) *= 3;
+        assert_eq!(registers.get('b'), 12);
+        assert_eq!(registers.source(Source::Register(1)), 12);
+        assert_eq!(registers.source(Source::Literal(-2)), -2);
+        assert_eq!(registers.load(0), 0);
+        assert_eq!(registers.to_map().len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn only_a_to_z_can_be_set() {
+        Registers::new().set('A', 1);
+    }
+}
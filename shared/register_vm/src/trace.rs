@@ -0,0 +1,255 @@
+use super::Register;
+use registers::Registers;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+// One executed instruction: `step` instructions had run before it, and
+// `delta` holds the new value of every register it changed.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub step: u64,
+    pub pc: usize,
+    pub instruction: String,
+    pub delta: BTreeMap<Register, i64>,
+}
+
+// The first line of a trace file.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    registers: BTreeMap<Register, i64>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Divergence {
+    // The two traces start from different registers.
+    InitialRegisters,
+    // The traces differ from `step` onwards. Either event is missing if that
+    // trace had already ended.
+    Step {
+        step: u64,
+        left: Option<Event>,
+        right: Option<Event>,
+    },
+}
+
+// A record of every instruction a program executed, written as JSON Lines:
+// a header holding the registers when tracing started, then one event per
+// line.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Trace {
+    initial: BTreeMap<Register, i64>,
+    events: Vec<Event>,
+    current: BTreeMap<Register, i64>,
+}
+
+impl Trace {
+    pub fn new(registers: &Registers) -> Trace {
+        let initial: BTreeMap<Register, i64> = registers.to_map().into_iter().collect();
+        Trace {
+            current: initial.clone(),
+            initial,
+            events: Vec::new(),
+        }
+    }
+
+    // Adds an event for the instruction at `pc`, given the registers after it
+    // was executed.
+    pub fn record(&mut self, pc: usize, instruction: String, registers: &Registers) {
+        let mut delta = BTreeMap::new();
+        for (register, value) in registers.to_map() {
+            if self.current.get(&register) != Some(&value) {
+                delta.insert(register, value);
+            }
+        }
+
+        self.current.extend(delta.iter().map(|(&r, &v)| (r, v)));
+        self.events.push(Event {
+            step: self.events.len() as u64,
+            pc,
+            instruction,
+            delta,
+        });
+    }
+
+    pub fn initial_registers(&self) -> &BTreeMap<Register, i64> {
+        &self.initial
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    // The registers after `step` instructions have been executed, or after
+    // the last one if the trace is shorter than that.
+    pub fn registers_at(&self, step: usize) -> BTreeMap<Register, i64> {
+        let mut registers = self.initial.clone();
+        for event in self.events.iter().take(step) {
+            registers.extend(event.delta.iter().map(|(&r, &v)| (r, v)));
+        }
+
+        registers
+    }
+
+    pub fn first_divergence(&self, other: &Trace) -> Option<Divergence> {
+        if self.initial != other.initial {
+            return Some(Divergence::InitialRegisters);
+        }
+
+        let length = self.events.len().max(other.events.len());
+        (0..length)
+            .find(|&n| self.events.get(n) != other.events.get(n))
+            .map(|n| Divergence::Step {
+                step: n as u64,
+                left: self.events.get(n).cloned(),
+                right: other.events.get(n).cloned(),
+            })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let header = Header {
+            registers: self.initial.clone(),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writeln!(writer)?;
+
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Trace> {
+        let mut lines = reader.lines();
+        let header: Header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "empty trace")),
+        };
+
+        let mut trace = Trace {
+            current: header.registers.clone(),
+            initial: header.registers,
+            events: Vec::new(),
+        };
+
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event: Event = serde_json::from_str(&line)?;
+            trace.current.extend(event.delta.iter().map(|(&r, &v)| (r, v)));
+            trace.events.push(event);
+        }
+
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counting_trace(limit: i64) -> Trace {
+        let mut registers = Registers::new();
+        registers.set('n', limit);
+        let mut trace = Trace::new(&registers);
+
+        for pc in 0..limit as usize {
+            *registers.get_mut('n') -= 1;
+            *registers.get_mut('t') += 2;
+            trace.record(pc, "count".to_string(), &registers);
+        }
+
+        trace
+    }
+
+    #[test]
+    fn record_changed_registers() {
+        let trace = counting_trace(3);
+        assert_eq!(trace.len(), 3);
+
+        let event = &trace.events()[1];
+        assert_eq!(event.step, 1);
+        assert_eq!(event.pc, 1);
+        assert_eq!(event.delta, vec![('n', 1), ('t', 4)].into_iter().collect());
+    }
+
+    #[test]
+    fn seek_to_step() {
+        let trace = counting_trace(3);
+        let registers = |step| trace.registers_at(step).into_iter().collect::<Vec<_>>();
+
+        assert_eq!(registers(0), vec![('n', 3)]);
+        assert_eq!(registers(2), vec![('n', 1), ('t', 4)]);
+        assert_eq!(registers(10), vec![('n', 0), ('t', 6)]);
+    }
+
+    #[test]
+    fn write_then_read() {
+        let trace = counting_trace(4);
+        let mut file = Vec::new();
+        trace.write(&mut file).unwrap();
+
+        let text = String::from_utf8(file.clone()).unwrap();
+        assert_eq!(text.lines().next(), Some(r#"{"registers":{"n":4}}"#));
+        assert_eq!(
+            text.lines().nth(1),
+            Some(r#"{"step":0,"pc":0,"instruction":"count","delta":{"n":3,"t":2}}"#)
+        );
+
+        assert_eq!(Trace::read(&file[..]).unwrap(), trace);
+    }
+
+    #[test]
+    fn reject_bad_lines() {
+        assert!(Trace::read(&b""[..]).is_err());
+        assert!(Trace::read(&b"{\"registers\":{}}\nnot json\n"[..]).is_err());
+    }
+
+    #[test]
+    fn find_first_divergence() {
+        let trace = counting_trace(4);
+        assert_eq!(trace.first_divergence(&trace), None);
+
+        let shorter = counting_trace(3);
+        assert_eq!(
+            trace.first_divergence(&shorter),
+            Some(Divergence::InitialRegisters)
+        );
+
+        let mut different = trace.clone();
+        different.events[2].delta.insert('t', 7);
+        assert_eq!(
+            trace.first_divergence(&different),
+            Some(Divergence::Step {
+                step: 2,
+                left: Some(trace.events()[2].clone()),
+                right: Some(different.events()[2].clone()),
+            })
+        );
+
+        let mut truncated = trace.clone();
+        truncated.events.pop();
+        assert_eq!(
+            trace.first_divergence(&truncated),
+            Some(Divergence::Step {
+                step: 3,
+                left: Some(trace.events()[3].clone()),
+                right: None,
+            })
+        );
+    }
+}